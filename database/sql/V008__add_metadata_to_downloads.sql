@@ -0,0 +1,4 @@
+ALTER TABLE downloads ADD COLUMN title text;
+ALTER TABLE downloads ADD COLUMN uploader text;
+ALTER TABLE downloads ADD COLUMN duration integer;
+ALTER TABLE downloads ADD COLUMN thumbnail text;
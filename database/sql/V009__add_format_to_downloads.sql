@@ -0,0 +1 @@
+ALTER TABLE downloads ADD COLUMN format text NOT NULL DEFAULT 'best-video';
@@ -0,0 +1,2 @@
+ALTER TABLE downloads ADD COLUMN audio_codec varchar(10);
+ALTER TABLE downloads ADD COLUMN audio_bitrate integer;
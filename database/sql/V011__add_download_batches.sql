@@ -0,0 +1,15 @@
+CREATE TABLE download_batches
+(
+    id           int GENERATED BY DEFAULT AS IDENTITY primary key,
+    batch_id     UUID        NOT NULL DEFAULT gen_random_uuid(),
+    link         text        NOT NULL,
+    title        text,
+    insert_time  timestamptz NOT NULL,
+    requester_id UUID        NOT NULL
+);
+
+CREATE INDEX CONCURRENTLY download_batches_batch_id_idx ON download_batches (batch_id);
+
+ALTER TABLE downloads ADD COLUMN batch_id UUID;
+
+CREATE INDEX CONCURRENTLY download_batch_id_idx ON downloads (batch_id)
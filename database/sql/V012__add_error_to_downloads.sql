@@ -0,0 +1,2 @@
+ALTER TABLE downloads ADD COLUMN error_kind varchar(40);
+ALTER TABLE downloads ADD COLUMN error_message text;
@@ -0,0 +1 @@
+ALTER TABLE downloads ADD COLUMN attempts integer NOT NULL DEFAULT 0;
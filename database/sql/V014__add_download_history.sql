@@ -0,0 +1,15 @@
+CREATE TABLE download_history
+(
+    id            int GENERATED BY DEFAULT AS IDENTITY primary key,
+    download_id   UUID        NOT NULL,
+    state         varchar(40) NOT NULL,
+    file          text,
+    percentage    integer,
+    attempts      integer     NOT NULL,
+    error_kind    varchar(40),
+    error_message text,
+    insert_time   timestamptz NOT NULL,
+    archived_time timestamptz NOT NULL
+);
+
+CREATE INDEX CONCURRENTLY download_history_download_id_idx ON download_history (download_id)
@@ -0,0 +1,3 @@
+ALTER TABLE downloads ADD COLUMN file_size integer;
+ALTER TABLE downloads ADD COLUMN pinned bool NOT NULL DEFAULT false;
+ALTER TABLE downloads ADD COLUMN expired_time timestamptz;
@@ -0,0 +1,3 @@
+ALTER TABLE downloads ADD COLUMN canonical_link text;
+
+CREATE INDEX CONCURRENTLY downloads_canonical_link_idx ON downloads (canonical_link)
@@ -0,0 +1,2 @@
+ALTER TABLE downloads ADD COLUMN storage_key text;
+ALTER TABLE downloads ADD COLUMN checksum varchar(64);
@@ -0,0 +1,10 @@
+CREATE TABLE outbox
+(
+    id          int GENERATED BY DEFAULT AS IDENTITY primary key,
+    subject     varchar(255) NOT NULL,
+    payload     bytea        NOT NULL,
+    insert_time timestamptz  NOT NULL,
+    sent_time   timestamptz
+);
+
+CREATE INDEX CONCURRENTLY outbox_pending_idx ON outbox (id) WHERE sent_time IS NULL
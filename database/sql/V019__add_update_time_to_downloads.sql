@@ -0,0 +1 @@
+ALTER TABLE downloads ADD COLUMN update_time timestamptz;
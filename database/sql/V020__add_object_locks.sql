@@ -0,0 +1,4 @@
+CREATE TABLE object_locks
+(
+    object_key text primary key
+);
\ No newline at end of file
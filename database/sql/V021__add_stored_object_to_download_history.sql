@@ -0,0 +1,3 @@
+ALTER TABLE download_history ADD COLUMN file_size integer;
+ALTER TABLE download_history ADD COLUMN storage_key text;
+ALTER TABLE download_history ADD COLUMN checksum varchar(64);
@@ -0,0 +1,4 @@
+CREATE TABLE job_locks
+(
+    job text primary key
+);
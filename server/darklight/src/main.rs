@@ -4,54 +4,215 @@ extern crate envconfig_derive;
 
 use std::sync::Arc;
 
+use clap::{Parser, Subcommand};
 use dotenv::dotenv;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_util::sync::CancellationToken;
 
+use darklight_api::readiness::{EventBusProbe, PostgresProbe, Probe, Readiness, StorageProbe, YtDlpProbe};
 use darklight_api::ApiDependencies;
 use darklight_app::download_queue::DownloadQueue;
 use darklight_app::file_downloader::FileDownloader;
+use darklight_app::worker_pool::WorkerPool;
+use darklight_events::event_bus::{EventPublisher, EventSubscriber};
+use darklight_events::in_memory_bus::InMemoryBus;
+use darklight_events::jetstream::JetStreamBus;
 use darklight_events::publisher::Publisher;
 use darklight_events::subscriber::subscriber::Subscriber;
+use darklight_events::EventBusCfg;
 use darklight_graphql::GraphQLDependencies;
+use darklight_handlers::outbox_relay::OutboxRelay;
+use darklight_handlers::recovery_policy::RecoveryPolicy;
+use darklight_handlers::retention_policy::RetentionPolicy;
+use darklight_handlers::retry_policy::RetryPolicy;
+use darklight_handlers::shutdown_policy::ShutdownPolicy;
+use darklight_handlers::state_gauges_job::StateGaugesJob;
 use darklight_handlers::HandlerDependencies;
+use darklight_persistence::postgres::PostgresDb;
+use darklight_persistence::repos::batches::BatchRepo;
 use darklight_persistence::repos::downloads::DownloadRepo;
-use darklight_storage::storage_downloader::S3StorageDownloader;
-use darklight_storage::storage_uploader::FileUploader;
+use darklight_persistence::repos::outbox::OutboxRepo;
+use darklight_storage::local_storage::LocalStorage;
+use darklight_storage::s3_storage::S3Storage;
+use darklight_storage::storage_backend::StorageBackend;
+use darklight_storage::StorageCfg;
+
+use crate::envconfig::Envconfig;
+
+#[derive(Parser)]
+#[clap(about = "Downloads videos and serves them")]
+struct Cli {
+    /// Defaults to running every role
+    #[clap(subcommand)]
+    role: Option<Role>,
+}
+
+#[derive(Subcommand, Clone, Copy, PartialEq)]
+enum Role {
+    /// Serves the REST API
+    ServeApi,
+    /// Serves the GraphQL API
+    ServeGraphql,
+    /// Runs the download workers, event handlers and background jobs
+    Worker,
+    /// Runs every role in a single process
+    All,
+}
+
+impl Role {
+    fn serves_api(self) -> bool {
+        matches!(self, Role::ServeApi | Role::All)
+    }
+
+    fn serves_graphql(self) -> bool {
+        matches!(self, Role::ServeGraphql | Role::All)
+    }
+
+    fn runs_workers(self) -> bool {
+        matches!(self, Role::Worker | Role::All)
+    }
+}
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
+    let role = Cli::parse().role.unwrap_or(Role::All);
+    let shutdown = CancellationToken::new();
+    tokio::spawn(cancel_on_signal(shutdown.clone()));
+
+    let postgres = Arc::new(PostgresDb::new_from_env().await.unwrap());
+    let download_repo = Arc::new(DownloadRepo::new(postgres.clone()));
+    let storage = connect_storage().await;
+    let (publisher, subscriber) = connect_event_bus(role).await;
+
+    let probes: Vec<Arc<dyn Probe>> = vec![
+        Arc::new(PostgresProbe::new(postgres.clone())),
+        Arc::new(EventBusProbe::new(publisher.clone(), subscriber.clone())),
+        Arc::new(StorageProbe::new(storage.clone())),
+        // workers download with yt-dlp, the api and graphql probe links with it
+        Arc::new(YtDlpProbe::new().await),
+    ];
+    let readiness = Arc::new(Readiness::new_from_env(probes).unwrap());
 
-    let download_repo = Arc::new(DownloadRepo::new_postgres().await.unwrap());
-    let file_uploader = Arc::new(FileUploader::new_from_env().await.unwrap());
-    let s3_storage_downloader = Arc::new(S3StorageDownloader::new_from_env().await.unwrap());
-    let publisher = Arc::new(Publisher::new_from_env().await.unwrap());
-    let subscriber = Arc::new(Subscriber::new_from_env().await.unwrap());
-    let download_queue = Arc::new(
-        DownloadQueue::new_from_env(
+    let worker_pool = role.runs_workers().then(|| Arc::new(WorkerPool::new_from_env().unwrap()));
+    let handler_deps = worker_pool.as_ref().map(|worker_pool| {
+        let outbox_repo = Arc::new(OutboxRepo::new(postgres.clone()));
+        let retry_policy = Arc::new(RetryPolicy::new_from_env().unwrap());
+        let recovery_policy = Arc::new(RecoveryPolicy::new_from_env(retry_policy.max_attempts).unwrap());
+
+        HandlerDependencies::new(
+            subscriber.clone().unwrap(),
             publisher.clone(),
+            Arc::new(FileDownloader::new_from_env(publisher.clone()).unwrap()),
+            storage.clone(),
             download_repo.clone(),
-            s3_storage_downloader.clone(),
+            retry_policy,
+            Arc::new(RetentionPolicy::new_from_env().unwrap()),
+            recovery_policy,
+            worker_pool.clone(),
+            Arc::new(ShutdownPolicy::new_from_env().unwrap()),
+            Arc::new(OutboxRelay::new_from_env(outbox_repo, publisher.clone()).unwrap()),
+            Arc::new(StateGaugesJob::new_from_env(download_repo.clone()).unwrap()),
         )
-        .unwrap(),
-    );
-    let file_downloader = Arc::new(FileDownloader::new_from_env(publisher.clone()).unwrap());
-    let handler_deps = HandlerDependencies::new(
-        subscriber.clone(),
-        publisher.clone(),
-        file_downloader.clone(),
-        file_uploader.clone(),
-        download_repo.clone(),
-    );
-    let api_deps = ApiDependencies::new_from_env(download_queue.clone()).unwrap();
-    let graphql_deps = GraphQLDependencies::new(
-        subscriber.clone(),
-        download_queue.clone(),
-        download_repo.clone(),
-    );
+    });
+
+    let download_queue = (role.serves_api() || role.serves_graphql()).then(|| {
+        Arc::new(
+            DownloadQueue::new_from_env(
+                download_repo.clone(),
+                Arc::new(BatchRepo::new(postgres.clone())),
+                storage.clone(),
+            )
+            .unwrap(),
+        )
+    });
+    let api_deps = role.serves_api().then(|| {
+        ApiDependencies::new_from_env(download_queue.clone().unwrap(), worker_pool.clone(), readiness.clone()).unwrap()
+    });
+    let graphql_deps = role.serves_graphql().then(|| {
+        GraphQLDependencies::new(
+            subscriber.clone().unwrap(),
+            download_queue.clone().unwrap(),
+            download_repo.clone(),
+        )
+    });
 
     let _ = tokio::join!(
-        darklight_handlers::run_handlers(handler_deps),
-        darklight_api::build(api_deps),
-        darklight_graphql::run(graphql_deps)
+        async {
+            if let Some(deps) = handler_deps {
+                darklight_handlers::run_handlers(deps, shutdown.clone()).await
+            }
+        },
+        async {
+            let served = match api_deps {
+                Some(deps) => darklight_api::build(deps, shutdown.clone()).await,
+                // workers still report their health, occupancy and metrics
+                None if role.runs_workers() => darklight_api::build_health(worker_pool.clone(), readiness.clone(), shutdown.clone()).await,
+                None => Ok(()),
+            };
+            if let Err(e) = served {
+                eprintln!("failed to serve api: {}", e)
+            }
+        },
+        async {
+            if let Some(deps) = graphql_deps {
+                darklight_graphql::run(deps, shutdown.clone()).await
+            }
+        },
     );
+
+    if let Err(e) = publisher.flush().await {
+        eprintln!("failed to flush events: {}", e)
+    }
+    postgres.close().await;
+    println!("shut down");
+}
+
+/// Starts shutting down on SIGTERM or SIGINT.
+async fn cancel_on_signal(shutdown: CancellationToken) {
+    let mut terminate = signal(SignalKind::terminate()).unwrap();
+    let mut interrupt = signal(SignalKind::interrupt()).unwrap();
+
+    tokio::select! {
+        _ = terminate.recv() => {}
+        _ = interrupt.recv() => {}
+    }
+    println!("shutting down");
+    shutdown.cancel();
+}
+
+async fn connect_storage() -> Arc<dyn StorageBackend> {
+    match StorageCfg::init_from_env().unwrap().backend.as_str() {
+        "s3" => Arc::new(S3Storage::new_from_env().await.unwrap()),
+        "local" => Arc::new(LocalStorage::new_from_env().await.unwrap()),
+        backend => panic!("unsupported storage backend: {}", backend),
+    }
+}
+
+/// Connects to the configured event bus, leaving out the subscriber when `role` doesn't need it.
+async fn connect_event_bus(role: Role) -> (Arc<dyn EventPublisher>, Option<Arc<dyn EventSubscriber>>) {
+    // only the workers and subscriptions of the graphql server consume events
+    let subscribe = role.runs_workers() || role.serves_graphql();
+
+    match EventBusCfg::init_from_env().unwrap().backend.as_str() {
+        "nats" => {
+            let subscriber: Option<Arc<dyn EventSubscriber>> = if subscribe {
+                Some(Arc::new(Subscriber::new_from_env().await.unwrap()))
+            } else {
+                None
+            };
+            (Arc::new(Publisher::new_from_env().await.unwrap()), subscriber)
+        }
+        "jetstream" => {
+            let bus = Arc::new(JetStreamBus::new_from_env().await.unwrap());
+            (bus.clone(), Some(bus))
+        }
+        // events published in one process would never reach the workers of another
+        "memory" if role != Role::All => panic!("the memory event bus only works with the `all` role"),
+        "memory" => {
+            let bus = Arc::new(InMemoryBus::new_from_env().unwrap());
+            (bus.clone(), Some(bus))
+        }
+        backend => panic!("unsupported event bus: {}", backend),
+    }
 }
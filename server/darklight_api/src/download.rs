@@ -2,18 +2,22 @@ use std::sync::Arc;
 
 use rocket::{
     fairing::AdHoc,
-    http::{Header, Method},
-    response::{self, status::NotFound, Responder},
+    http::Method,
+    response::{status::BadRequest, status::Created, status::NotFound},
     serde::{json::Json, Deserialize, Serialize},
-    Request, State,
+    State,
 };
 use rocket_cors::AllowedOrigins;
 
-use darklight_app::download_queue::DownloadQueue;
-use darklight_core::download::Download;
+use darklight_app::download_queue::{DownloadQueue, QueuedDownload};
+use darklight_core::download::{Download, DownloadRun};
+use darklight_core::download_batch::DownloadBatch;
+use darklight_core::download_options::{AudioExtraction, DownloadOptions, FormatPreset};
 use darklight_core::download_state::DownloadState;
+use darklight_core::share_link::ShareLink;
 
 use crate::api_config::ApiConfig;
+use crate::file_response::{DownloadedFile, RangeHeaders, RangeNotSatisfiable, RequestedRange};
 
 type Downloads<'r> = &'r State<Arc<DownloadQueue>>;
 
@@ -22,6 +26,10 @@ type Downloads<'r> = &'r State<Arc<DownloadQueue>>;
 struct DownloadRequest<'r> {
     link: &'r str,
     requester_id: String,
+    #[serde(default)]
+    format: FormatPreset,
+    #[serde(default)]
+    audio: Option<AudioExtraction>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -32,6 +40,59 @@ struct DownloadResponse {
     state: String,
     file_name: Option<String>,
     percentage: u32,
+    error_kind: Option<String>,
+    error: Option<String>,
+    attempts: u32,
+    file_size: Option<u64>,
+    pinned: bool,
+    checksum: Option<String>,
+}
+
+/// A single download is answered with its bare id. A playlist is answered with the id of its batch,
+/// created at the batch resource its downloads are looked up through.
+#[derive(Responder)]
+enum QueuedResponse {
+    Download(String),
+    Batch(Created<String>),
+}
+
+impl From<QueuedDownload> for QueuedResponse {
+    fn from(queued: QueuedDownload) -> Self {
+        match queued {
+            QueuedDownload::Download(id) => Self::Download(id),
+            QueuedDownload::Batch(id) => Self::Batch(Created::new(format!("/api/batch/{}", id)).body(id)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct BatchResponse {
+    id: String,
+    link: String,
+    title: Option<String>,
+    total: u32,
+    done: u32,
+    failed: u32,
+    percentage: u32,
+    downloads: Vec<DownloadResponse>,
+}
+
+impl From<DownloadBatch> for BatchResponse {
+    fn from(batch: DownloadBatch) -> Self {
+        let progress = batch.progress();
+
+        Self {
+            id: batch.id.unwrap(),
+            link: batch.link,
+            title: batch.title,
+            total: progress.total,
+            done: progress.done,
+            failed: progress.failed,
+            percentage: progress.percentage,
+            downloads: batch.downloads.into_iter().map(DownloadResponse::from).collect(),
+        }
+    }
 }
 
 impl From<Download> for DownloadResponse {
@@ -43,10 +104,18 @@ impl From<Download> for DownloadResponse {
                 DownloadState::Downloading => "downloading".into(),
                 DownloadState::Done => "done".into(),
                 DownloadState::Error => "error".into(),
+                DownloadState::Cancelled => "cancelled".into(),
+                DownloadState::Expired => "expired".into(),
             },
             link: download.link,
             file_name: download.file,
             percentage: download.percentage,
+            error_kind: download.error.as_ref().map(|e| e.kind.as_str().to_string()),
+            error: download.error.map(|e| e.message),
+            attempts: download.attempts,
+            file_size: download.file_size,
+            pinned: download.pinned,
+            checksum: download.checksum,
         }
     }
 }
@@ -55,13 +124,31 @@ impl From<Download> for DownloadResponse {
 async fn request_download(
     downloads: Downloads<'_>,
     download_request: Json<DownloadRequest<'_>>,
-) -> String {
+) -> Result<QueuedResponse, BadRequest<String>> {
     match downloads
-        .add(download_request.link, download_request.requester_id.clone())
+        .add(
+            download_request.link,
+            download_request.requester_id.clone(),
+            DownloadOptions {
+                format: download_request.format.clone(),
+                audio: download_request.audio.clone(),
+            },
+        )
         .await
     {
-        Ok(download_id) => download_id,
-        Err(e) => format!("{}", e),
+        Ok(queued) => Ok(queued.into()),
+        Err(e) => Err(BadRequest(Some(e.to_string()))),
+    }
+}
+
+#[get("/<batch_id>")]
+async fn get_batch(
+    batch_id: &str,
+    downloads: Downloads<'_>,
+) -> Result<Json<BatchResponse>, NotFound<String>> {
+    match downloads.get_batch(batch_id).await {
+        Ok(Some(batch)) => Ok(Json(batch.into())),
+        Ok(None) | Err(..) => Err(NotFound("could not find batch".into())),
     }
 }
 
@@ -77,32 +164,105 @@ async fn get_request_download(
     }
 }
 
-struct DownloadedFile {
-    file_name: String,
-    file_data: Vec<u8>,
+#[delete("/<download_id>")]
+async fn cancel_download(
+    download_id: &str,
+    downloads: Downloads<'_>,
+) -> Result<(), BadRequest<String>> {
+    match downloads.cancel(download_id).await {
+        Ok(_) => Ok(()),
+        Err(e) => Err(BadRequest(Some(e.to_string()))),
+    }
+}
+
+#[post("/<download_id>/retry")]
+async fn retry_download(
+    download_id: &str,
+    downloads: Downloads<'_>,
+) -> Result<(), BadRequest<String>> {
+    match downloads.retry(download_id).await {
+        Ok(_) => Ok(()),
+        Err(e) => Err(BadRequest(Some(e.to_string()))),
+    }
+}
+
+#[post("/<download_id>/pin")]
+async fn pin_download(
+    download_id: &str,
+    downloads: Downloads<'_>,
+) -> Result<(), BadRequest<String>> {
+    match downloads.pin(download_id, true).await {
+        Ok(_) => Ok(()),
+        Err(e) => Err(BadRequest(Some(e.to_string()))),
+    }
 }
 
-impl<'r> Responder<'r, 'static> for DownloadedFile {
-    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
-        let mut response = self.file_data.respond_to(req)?;
-        response.set_header(Header::new(
-            "Content-Disposition",
-            format!("attachment; filename=\"{}\"", self.file_name),
-        ));
-        Ok(response)
+#[delete("/<download_id>/pin")]
+async fn unpin_download(
+    download_id: &str,
+    downloads: Downloads<'_>,
+) -> Result<(), BadRequest<String>> {
+    match downloads.pin(download_id, false).await {
+        Ok(_) => Ok(()),
+        Err(e) => Err(BadRequest(Some(e.to_string()))),
     }
 }
 
-#[get("/<download_id>/file")]
+#[get("/<download_id>/history")]
+async fn get_download_history(
+    download_id: &str,
+    downloads: Downloads<'_>,
+) -> Result<Json<Vec<DownloadRun>>, BadRequest<String>> {
+    match downloads.get_history(download_id).await {
+        Ok(runs) => Ok(Json(runs)),
+        Err(e) => Err(BadRequest(Some(e.to_string()))),
+    }
+}
+
+#[get("/<download_id>/share?<ttl>")]
+async fn get_share_link(
+    download_id: &str,
+    ttl: Option<u32>,
+    downloads: Downloads<'_>,
+) -> Result<Json<ShareLink>, BadRequest<String>> {
+    match downloads.share_link(download_id, ttl).await {
+        Ok(link) => Ok(Json(link)),
+        Err(e) => Err(BadRequest(Some(e.to_string()))),
+    }
+}
+
+#[derive(Responder)]
+enum FileResponse {
+    File(DownloadedFile),
+    RangeNotSatisfiable(RangeNotSatisfiable),
+}
+
+#[get("/<download_id>/file?<inline>")]
 async fn get_downloaded_file<'a>(
     download_id: &'a str,
+    inline: Option<bool>,
+    range_headers: RangeHeaders<'a>,
     downloads: Downloads<'a>,
-) -> Result<DownloadedFile, NotFound<String>> {
-    match downloads.get_file(download_id).await {
-        Ok(Some((file_name, file_data))) => Ok(DownloadedFile {
+) -> Result<FileResponse, NotFound<String>> {
+    let info = match downloads.get_file_info(download_id).await {
+        Ok(Some((_, info))) => info,
+        Ok(None) | Err(..) => return Err(NotFound("could not find download".into())),
+    };
+
+    let range = match range_headers.requested_range(&info) {
+        RequestedRange::Full => None,
+        RequestedRange::Partial(range) => Some(range),
+        RequestedRange::Unsatisfiable => {
+            return Ok(FileResponse::RangeNotSatisfiable(RangeNotSatisfiable(info.size)))
+        }
+    };
+
+    match downloads.get_file(download_id, range).await {
+        Ok(Some((file_name, file))) => Ok(FileResponse::File(DownloadedFile {
             file_name,
-            file_data,
-        }),
+            file,
+            inline: inline.unwrap_or(false),
+        })),
         Ok(None) => Err(NotFound("could not find download".into())),
         Err(..) => Err(NotFound("could not find download".into())),
     }
@@ -113,7 +273,7 @@ pub fn stage(download_queue: Arc<DownloadQueue>, cfg: Arc<ApiConfig>) -> AdHoc {
 
     let cors = rocket_cors::CorsOptions {
         allowed_origins,
-        allowed_methods: vec![Method::Post].into_iter().map(From::from).collect(),
+        allowed_methods: vec![Method::Post, Method::Delete].into_iter().map(From::from).collect(),
         ..Default::default()
     }
     .to_cors();
@@ -122,8 +282,9 @@ pub fn stage(download_queue: Arc<DownloadQueue>, cfg: Arc<ApiConfig>) -> AdHoc {
         rocket
             .mount(
                 "/api/download",
-                routes![request_download, get_request_download, get_downloaded_file],
+                routes![request_download, get_request_download, get_downloaded_file, cancel_download, retry_download, get_download_history, get_share_link, pin_download, unpin_download],
             )
+            .mount("/api/batch", routes![get_batch])
             .manage(download_queue)
             .attach(cors.unwrap())
     })
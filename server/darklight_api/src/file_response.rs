@@ -0,0 +1,195 @@
+use std::path::Path;
+
+use rocket::{
+    http::{ContentType, Header, Status},
+    request::{self, FromRequest},
+    response::{self, Responder, Response},
+    Request,
+};
+
+use darklight_storage::storage_backend::{content_disposition, ByteRange, ObjectInfo, StoredObject};
+
+/// A single range from a `Range: bytes=...` header, before it is resolved against the file size.
+#[derive(Debug, PartialEq)]
+pub enum RangeSpec {
+    From(u64),
+    Bounded(u64, u64),
+    Suffix(u64),
+}
+
+impl RangeSpec {
+    /// Returns `None` if the range does not overlap the file.
+    pub fn resolve(&self, size: u64) -> Option<ByteRange> {
+        match *self {
+            RangeSpec::From(start) if start < size => Some(ByteRange { start, end: size - 1 }),
+            RangeSpec::Bounded(start, end) if start < size => Some(ByteRange {
+                start,
+                end: end.min(size - 1),
+            }),
+            RangeSpec::Suffix(length) if length > 0 && size > 0 => Some(ByteRange {
+                start: size - length.min(size),
+                end: size - 1,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Malformed headers and requests for multiple ranges are ignored, so the whole file is served.
+pub fn parse_range(header: &str) -> Option<RangeSpec> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+
+    let (start, end) = spec.trim().split_once('-')?;
+    match (start.parse::<u64>().ok(), end.parse::<u64>().ok()) {
+        (Some(start), None) if end.is_empty() => Some(RangeSpec::From(start)),
+        (Some(start), Some(end)) if start <= end => Some(RangeSpec::Bounded(start, end)),
+        (None, Some(length)) if start.is_empty() => Some(RangeSpec::Suffix(length)),
+        _ => None,
+    }
+}
+
+pub enum RequestedRange {
+    Full,
+    Partial(ByteRange),
+    Unsatisfiable,
+}
+
+pub struct RangeHeaders<'r> {
+    range: Option<&'r str>,
+    if_range: Option<&'r str>,
+}
+
+impl RangeHeaders<'_> {
+    pub fn requested_range(&self, info: &ObjectInfo) -> RequestedRange {
+        let spec = match self.range.and_then(parse_range) {
+            Some(spec) => spec,
+            None => return RequestedRange::Full,
+        };
+
+        // a range is only valid for the version of the file the client already has part of
+        if let Some(if_range) = self.if_range {
+            match &info.etag {
+                Some(etag) if !etag.starts_with("W/") && etag == if_range => {}
+                _ => return RequestedRange::Full,
+            }
+        }
+
+        match spec.resolve(info.size) {
+            Some(range) => RequestedRange::Partial(range),
+            None => RequestedRange::Unsatisfiable,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RangeHeaders<'r> {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(RangeHeaders {
+            range: req.headers().get_one("Range"),
+            if_range: req.headers().get_one("If-Range"),
+        })
+    }
+}
+
+pub struct DownloadedFile {
+    pub file_name: String,
+    pub file: StoredObject,
+    pub inline: bool,
+}
+
+impl<'r> Responder<'r, 'static> for DownloadedFile {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let disposition = if self.inline { "inline" } else { "attachment" };
+        let size = self.file.info.size;
+
+        let mut response = Response::build();
+        response
+            .header(content_type(&self.file_name))
+            .header(Header::new("Accept-Ranges", "bytes"))
+            .header(Header::new("Content-Disposition", content_disposition(disposition, &self.file_name)));
+        if let Some(etag) = self.file.info.etag {
+            response.header(Header::new("ETag", etag));
+        }
+
+        // the body is streamed, so the length has to be set explicitly
+        match self.file.range {
+            Some(range) => response
+                .status(Status::PartialContent)
+                .header(Header::new(
+                    "Content-Range",
+                    format!("bytes {}-{}/{}", range.start, range.end, size),
+                ))
+                .header(Header::new("Content-Length", (range.end - range.start + 1).to_string())),
+            None => response.header(Header::new("Content-Length", size.to_string())),
+        };
+
+        // the object is piped to the client as it is read from storage
+        response.streamed_body(self.file.reader).ok()
+    }
+}
+
+pub struct RangeNotSatisfiable(pub u64);
+
+impl<'r> Responder<'r, 'static> for RangeNotSatisfiable {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .status(Status::RangeNotSatisfiable)
+            .header(Header::new("Content-Range", format!("bytes */{}", self.0)))
+            .ok()
+    }
+}
+
+pub fn content_type(file_name: &str) -> ContentType {
+    let extension = Path::new(file_name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "mp3" => ContentType::new("audio", "mpeg"),
+        "m4a" => ContentType::new("audio", "mp4"),
+        "opus" => ContentType::new("audio", "ogg"),
+        "mkv" => ContentType::new("video", "x-matroska"),
+        ext => ContentType::from_extension(ext).unwrap_or(ContentType::Binary),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::ContentType;
+
+    use darklight_storage::storage_backend::ByteRange;
+
+    use crate::file_response::{content_type, parse_range, RangeSpec};
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-499"), Some(RangeSpec::Bounded(0, 499)));
+        assert_eq!(parse_range("bytes=500-"), Some(RangeSpec::From(500)));
+        assert_eq!(parse_range("bytes=-500"), Some(RangeSpec::Suffix(500)));
+        assert_eq!(parse_range("bytes=500-100"), None);
+        assert_eq!(parse_range("bytes=0-1,5-6"), None);
+        assert_eq!(parse_range("items=0-1"), None);
+    }
+
+    #[test]
+    fn test_resolve_range() {
+        assert_eq!(RangeSpec::Bounded(0, 499).resolve(100), Some(ByteRange { start: 0, end: 99 }));
+        assert_eq!(RangeSpec::From(90).resolve(100), Some(ByteRange { start: 90, end: 99 }));
+        assert_eq!(RangeSpec::Suffix(500).resolve(100), Some(ByteRange { start: 0, end: 99 }));
+        assert_eq!(RangeSpec::From(100).resolve(100), None);
+        assert_eq!(RangeSpec::Suffix(0).resolve(100), None);
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(content_type("video.mp4"), ContentType::new("video", "mp4"));
+        assert_eq!(content_type("song.MP3"), ContentType::new("audio", "mpeg"));
+        assert_eq!(content_type("unknown"), ContentType::Binary);
+    }
+}
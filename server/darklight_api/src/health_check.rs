@@ -1,12 +1,57 @@
-use rocket::fairing::AdHoc;
+use std::sync::Arc;
+
+use rocket::{fairing::AdHoc, http::Status, serde::json::Json, serde::Serialize, State};
+
+use darklight_app::worker_pool::{Occupancy, WorkerPool};
+
+use crate::readiness::{Readiness, ReadinessReport};
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ProcessStatus {
+    /// Absent when this process doesn't run download workers
+    workers: Option<Occupancy>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Liveness {
+    alive: bool,
+}
 
 #[get("/healthz")]
 pub fn get_health_check() -> String {
     "Ok!".into()
 }
 
-pub fn stage() -> AdHoc {
+/// Only tells whether the process still answers. Dependencies are left to `/readyz`, as
+/// restarting the process doesn't bring them back.
+#[get("/livez")]
+fn get_liveness() -> Json<Liveness> {
+    Json(Liveness { alive: true })
+}
+
+/// Fails while any dependency is unavailable, so no traffic is routed to this process.
+#[get("/readyz")]
+async fn get_readiness(readiness: &State<Arc<Readiness>>) -> (Status, Json<ReadinessReport>) {
+    let report = readiness.check().await;
+    let status = if report.ready { Status::Ok } else { Status::ServiceUnavailable };
+
+    (status, Json(report))
+}
+
+#[get("/status")]
+fn get_status(worker_pool: &State<Option<Arc<WorkerPool>>>) -> Json<ProcessStatus> {
+    Json(ProcessStatus {
+        workers: worker_pool.as_ref().map(|pool| pool.occupancy()),
+    })
+}
+
+pub fn stage(worker_pool: Option<Arc<WorkerPool>>, readiness: Arc<Readiness>) -> AdHoc {
     AdHoc::on_ignite("base", |rocket| async {
-        rocket.mount("/api", routes![get_health_check])
+        rocket
+            .manage(worker_pool)
+            .manage(readiness)
+            .mount("/api", routes![get_health_check, get_liveness, get_readiness, get_status])
     })
 }
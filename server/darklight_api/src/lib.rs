@@ -6,41 +6,84 @@ extern crate rocket;
 use std::error::Error;
 use std::sync::Arc;
 
+use rocket::{Build, Rocket};
+use tokio_util::sync::CancellationToken;
+
 use darklight_app::download_queue::DownloadQueue;
+use darklight_app::worker_pool::WorkerPool;
 
 use crate::api_config::ApiConfig;
+use crate::readiness::Readiness;
 use crate::envconfig::Envconfig;
 
-mod health_check;
-mod download;
+pub mod health_check;
+pub mod download;
+mod file_response;
+pub mod metrics;
 pub mod api_config;
+pub mod readiness;
 
 pub struct ApiDependencies {
     cfg: Arc<ApiConfig>,
     download_queue: Arc<DownloadQueue>,
+    worker_pool: Option<Arc<WorkerPool>>,
+    readiness: Arc<Readiness>,
 }
 
 impl ApiDependencies {
-    pub fn new(cfg: Arc<ApiConfig>, download_queue: Arc<DownloadQueue>) -> Self {
+    /// `worker_pool` is reported on by the status and metrics endpoints, when this process runs workers.
+    pub fn new(cfg: Arc<ApiConfig>, download_queue: Arc<DownloadQueue>, worker_pool: Option<Arc<WorkerPool>>, readiness: Arc<Readiness>) -> Self {
         Self {
             cfg,
             download_queue,
+            worker_pool,
+            readiness,
         }
     }
 
-    pub fn new_from_env(download_queue: Arc<DownloadQueue>) -> Result<Self, Box<dyn Error>> {
+    pub fn new_from_env(download_queue: Arc<DownloadQueue>, worker_pool: Option<Arc<WorkerPool>>, readiness: Arc<Readiness>) -> Result<Self, Box<dyn Error>> {
         let api_cfg = Arc::new(api_config::ApiConfig::init_from_env()?);
-        Ok(Self::new(api_cfg, download_queue))
+        Ok(Self::new(api_cfg, download_queue, worker_pool, readiness))
     }
 }
 
-pub async fn build(deps: ApiDependencies) -> Result<(), Box<dyn Error>> {
-    match rocket::build()
-        .attach(health_check::stage())
-        .attach(download::stage(deps.download_queue.clone(), deps.cfg.clone()))
-        .launch().await {
-        Ok(_) => { Ok(()) }
-        Err(e) => { Err(e.into()) }
-    }
+/// Serves until `shutdown` is cancelled, letting requests in progress finish.
+pub async fn build(deps: ApiDependencies, shutdown: CancellationToken) -> Result<(), Box<dyn Error>> {
+    let rocket = configure()
+        .attach(health_check::stage(deps.worker_pool.clone(), deps.readiness.clone()))
+        .attach(metrics::stage(deps.worker_pool.clone()))
+        .attach(download::stage(deps.download_queue.clone(), deps.cfg.clone()));
+
+    launch(rocket, shutdown).await
+}
+
+/// Serves only the health, status and metrics endpoints, for processes which don't serve the API.
+pub async fn build_health(worker_pool: Option<Arc<WorkerPool>>, readiness: Arc<Readiness>, shutdown: CancellationToken) -> Result<(), Box<dyn Error>> {
+    let rocket = configure()
+        .attach(health_check::stage(worker_pool.clone(), readiness))
+        .attach(metrics::stage(worker_pool));
+
+    launch(rocket, shutdown).await
 }
 
+fn configure() -> Rocket<Build> {
+    // the process decides when to shut down, so every part of it stops together
+    let figment = rocket::Config::figment()
+        .merge(("shutdown.ctrlc", false))
+        .merge(("shutdown.signals", Vec::<String>::new()));
+
+    rocket::custom(figment)
+}
+
+async fn launch(rocket: Rocket<Build>, shutdown: CancellationToken) -> Result<(), Box<dyn Error>> {
+    let rocket = rocket.ignite().await?;
+    let handle = rocket.shutdown();
+    tokio::spawn(async move {
+        shutdown.cancelled().await;
+        handle.notify();
+    });
+
+    rocket.launch().await?;
+
+    Ok(())
+}
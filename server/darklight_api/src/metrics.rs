@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use rocket::{fairing::AdHoc, http::ContentType, http::Status, response::status::Custom, State};
+
+use darklight_app::worker_pool::WorkerPool;
+
+/// Source of the occupancy gauges, which are read on every scrape instead of being kept up to date.
+/// The gauges of stored downloads are kept up to date by the workers.
+struct Gauges {
+    worker_pool: Option<Arc<WorkerPool>>,
+}
+
+impl Gauges {
+    fn refresh(&self) {
+        if let Some(worker_pool) = &self.worker_pool {
+            let occupancy = worker_pool.occupancy();
+            darklight_metrics::WORKER_IN_FLIGHT.set(occupancy.in_flight as i64);
+            darklight_metrics::WORKER_RUNNING.set(occupancy.running as i64);
+        }
+    }
+}
+
+#[get("/metrics")]
+fn get_metrics(gauges: &State<Gauges>) -> Result<(ContentType, String), Custom<String>> {
+    gauges.refresh();
+
+    match darklight_metrics::encode() {
+        Ok(encoded) => Ok((ContentType::parse_flexible(darklight_metrics::CONTENT_TYPE).unwrap_or(ContentType::Plain), encoded)),
+        Err(e) => Err(Custom(Status::InternalServerError, e.to_string())),
+    }
+}
+
+pub fn stage(worker_pool: Option<Arc<WorkerPool>>) -> AdHoc {
+    AdHoc::on_ignite("metrics", |rocket| async {
+        rocket
+            .manage(Gauges { worker_pool })
+            .mount("/", routes![get_metrics])
+    })
+}
@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use futures::future;
+use rocket::serde::Serialize;
+use tokio::sync::OnceCell;
+
+use darklight_events::event_bus::{EventPublisher, EventSubscriber};
+use darklight_persistence::postgres::PostgresDb;
+use darklight_storage::storage_backend::StorageBackend;
+use darklight_ytd::youtube_dl;
+
+use crate::envconfig::Envconfig;
+
+#[derive(Envconfig)]
+pub struct ReadinessCfg {
+    /// How long a single dependency may take to answer before it counts as failing
+    #[envconfig(from = "READINESS_PROBE_TIMEOUT_MS", default = "2000")]
+    pub probe_timeout_ms: u64,
+}
+
+/// Checks a dependency the process can't serve without.
+#[async_trait]
+pub trait Probe: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Returns details worth reporting, like a version.
+    async fn check(&self) -> Result<Option<String>, Box<dyn Error>>;
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct DependencyStatus {
+    pub ready: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub dependencies: BTreeMap<&'static str, DependencyStatus>,
+}
+
+pub struct Readiness {
+    probe_timeout: Duration,
+    probes: Vec<Arc<dyn Probe>>,
+}
+
+impl Readiness {
+    pub fn new(cfg: &ReadinessCfg, probes: Vec<Arc<dyn Probe>>) -> Self {
+        Self {
+            probe_timeout: Duration::from_millis(cfg.probe_timeout_ms),
+            probes,
+        }
+    }
+
+    pub fn new_from_env(probes: Vec<Arc<dyn Probe>>) -> Result<Self, Box<dyn Error>> {
+        let readiness_cfg = ReadinessCfg::init_from_env()?;
+        Ok(Self::new(&readiness_cfg, probes))
+    }
+
+    /// Runs every probe at once, so one hanging dependency doesn't hide the others.
+    pub async fn check(&self) -> ReadinessReport {
+        let statuses = future::join_all(self.probes.iter().map(|probe| async move {
+            let started = Instant::now();
+            let result = match tokio::time::timeout(self.probe_timeout, probe.check()).await {
+                Ok(Ok(detail)) => Ok(detail),
+                Ok(Err(e)) => Err(e.to_string()),
+                Err(_) => Err(format!("no answer within {:?}", self.probe_timeout)),
+            };
+            let latency_ms = started.elapsed().as_millis() as u64;
+
+            let status = match result {
+                Ok(detail) => DependencyStatus { ready: true, latency_ms, detail, error: None },
+                Err(e) => DependencyStatus { ready: false, latency_ms, detail: None, error: Some(e) },
+            };
+            (probe.name(), status)
+        }))
+        .await;
+
+        ReadinessReport {
+            ready: statuses.iter().all(|(_, status)| status.ready),
+            dependencies: statuses.into_iter().collect(),
+        }
+    }
+}
+
+pub struct PostgresProbe {
+    postgres: Arc<PostgresDb>,
+}
+
+impl PostgresProbe {
+    pub fn new(postgres: Arc<PostgresDb>) -> Self {
+        Self { postgres }
+    }
+}
+
+#[async_trait]
+impl Probe for PostgresProbe {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    async fn check(&self) -> Result<Option<String>, Box<dyn Error>> {
+        self.postgres.ping().await?;
+
+        Ok(None)
+    }
+}
+
+pub struct EventBusProbe {
+    publisher: Arc<dyn EventPublisher>,
+    subscriber: Option<Arc<dyn EventSubscriber>>,
+}
+
+impl EventBusProbe {
+    /// `subscriber` is left out by processes which don't consume events.
+    pub fn new(publisher: Arc<dyn EventPublisher>, subscriber: Option<Arc<dyn EventSubscriber>>) -> Self {
+        Self { publisher, subscriber }
+    }
+}
+
+#[async_trait]
+impl Probe for EventBusProbe {
+    fn name(&self) -> &'static str {
+        "event_bus"
+    }
+
+    async fn check(&self) -> Result<Option<String>, Box<dyn Error>> {
+        self.publisher.ping().await?;
+        if let Some(subscriber) = &self.subscriber {
+            subscriber.ping().await?;
+        }
+
+        Ok(None)
+    }
+}
+
+pub struct StorageProbe {
+    storage: Arc<dyn StorageBackend>,
+}
+
+impl StorageProbe {
+    pub fn new(storage: Arc<dyn StorageBackend>) -> Self {
+        Self { storage }
+    }
+}
+
+#[async_trait]
+impl Probe for StorageProbe {
+    fn name(&self) -> &'static str {
+        "storage"
+    }
+
+    async fn check(&self) -> Result<Option<String>, Box<dyn Error>> {
+        self.storage.ping().await?;
+
+        Ok(None)
+    }
+}
+
+/// Only needed by processes which run downloads. Running yt-dlp takes long enough to fail the
+/// probe on a busy worker, so its version is read once and only its binary is looked up afterwards.
+pub struct YtDlpProbe {
+    version: OnceCell<String>,
+}
+
+impl YtDlpProbe {
+    pub async fn new() -> Self {
+        let probe = Self { version: OnceCell::new() };
+        if let Err(e) = probe.version().await {
+            eprintln!("failed to read yt-dlp version: {}", e)
+        }
+
+        probe
+    }
+
+    async fn version(&self) -> Result<&String, String> {
+        self.version
+            .get_or_try_init(|| async { youtube_dl::version().await.map_err(|e| e.to_string()) })
+            .await
+    }
+}
+
+#[async_trait]
+impl Probe for YtDlpProbe {
+    fn name(&self) -> &'static str {
+        "yt-dlp"
+    }
+
+    async fn check(&self) -> Result<Option<String>, Box<dyn Error>> {
+        if youtube_dl::binary_path().is_none() {
+            return Err("yt-dlp was not found".into());
+        }
+
+        Ok(Some(self.version().await?.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+
+    use crate::readiness::{Probe, Readiness, ReadinessCfg};
+
+    struct FakeProbe {
+        name: &'static str,
+        delay: Duration,
+        fails: bool,
+    }
+
+    #[async_trait]
+    impl Probe for FakeProbe {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn check(&self) -> Result<Option<String>, Box<dyn Error>> {
+            tokio::time::sleep(self.delay).await;
+            if self.fails {
+                return Err("connection refused".into());
+            }
+
+            Ok(Some("1.0".to_string()))
+        }
+    }
+
+    fn readiness(probes: Vec<FakeProbe>) -> Readiness {
+        let probes = probes.into_iter().map(|p| Arc::new(p) as Arc<dyn Probe>).collect();
+        Readiness::new(&ReadinessCfg { probe_timeout_ms: 50 }, probes)
+    }
+
+    #[tokio::test]
+    async fn test_ready_when_every_probe_passes() {
+        let report = readiness(vec![
+            FakeProbe { name: "a", delay: Duration::ZERO, fails: false },
+            FakeProbe { name: "b", delay: Duration::ZERO, fails: false },
+        ]).check().await;
+
+        assert!(report.ready);
+        assert_eq!(report.dependencies["a"].detail.as_deref(), Some("1.0"));
+    }
+
+    #[tokio::test]
+    async fn test_failing_and_hanging_probes_are_reported() {
+        let report = readiness(vec![
+            FakeProbe { name: "ok", delay: Duration::ZERO, fails: false },
+            FakeProbe { name: "failing", delay: Duration::ZERO, fails: true },
+            FakeProbe { name: "hanging", delay: Duration::from_secs(5), fails: false },
+        ]).check().await;
+
+        assert!(!report.ready);
+        assert!(report.dependencies["ok"].ready);
+        assert_eq!(report.dependencies["failing"].error.as_deref(), Some("connection refused"));
+        assert!(!report.dependencies["hanging"].ready);
+    }
+}
@@ -1,15 +1,22 @@
-use std::{collections::HashMap, error::Error, sync::Arc};
+use std::{collections::HashSet, error::Error, path::PathBuf, sync::Arc};
 
-use chrono::{DateTime, Utc};
-use tokio::sync::Mutex;
-use tokio::task;
+use chrono::{Duration, Utc};
 
-use darklight_core::download::Download;
+use darklight_core::canonical_link::canonicalize_link;
+use darklight_core::download::{Download, DownloadMetadata, DownloadRun};
+use darklight_core::download_batch::DownloadBatch;
+use darklight_core::download_options::DownloadOptions;
 use darklight_core::download_state::DownloadState;
-use darklight_events::events;
-use darklight_events::publisher::Publisher;
+use darklight_core::share_link::ShareLink;
+use darklight_events::envelope::Event;
+use darklight_events::models::DownloadCancel;
+use darklight_persistence::repos::batches::BatchRepo;
 use darklight_persistence::repos::downloads::DownloadRepo;
-use darklight_storage::storage_downloader::S3StorageDownloader;
+use darklight_persistence::repos::outbox::OutboxMessage;
+use darklight_storage::checksum::ChecksumReader;
+use darklight_storage::storage_backend::{ByteRange, ObjectInfo, StorageBackend, StoredObject};
+use darklight_ytd::media_info::{flatten_entries, MediaInfo, PlaylistEntry};
+use darklight_ytd::youtube_dl::YoutubeDL;
 
 use crate::envconfig::Envconfig;
 
@@ -17,65 +24,272 @@ use crate::envconfig::Envconfig;
 pub struct DownloadQueueCfg {
     #[envconfig(from = "STORAGE_PATH", default = "./target/output")]
     pub storage_path: String,
+
+    #[envconfig(from = "SHARE_LINK_DEFAULT_TTL_SECS", default = "3600")]
+    pub share_link_default_ttl_secs: u32,
+
+    // presigned S3 urls can be valid for at most 7 days
+    #[envconfig(from = "SHARE_LINK_MAX_TTL_SECS", default = "604800")]
+    pub share_link_max_ttl_secs: u32,
+
+    // probing runs yt-dlp while the request waits for it
+    #[envconfig(from = "PROBE_TIMEOUT_SECS", default = "30")]
+    pub probe_timeout_secs: u64,
+}
+
+pub enum QueuedDownload {
+    Download(String),
+    Batch(String),
+}
+
+struct StoredFile {
+    name: String,
+    key: String,
+    checksum: Option<String>,
+}
+
+impl StoredFile {
+    // the checksum identifies the content regardless of which storage backend holds it
+    fn etag(&self) -> Option<String> {
+        self.checksum.as_ref().map(|c| format!("\"{}\"", c))
+    }
 }
 
 pub struct DownloadQueue {
-    downloads: Arc<Mutex<HashMap<String, Download>>>,
     cfg: Arc<DownloadQueueCfg>,
-    publisher: Arc<Publisher>,
     download_repo: Arc<DownloadRepo>,
-    storage_downloader: Arc<S3StorageDownloader>,
+    batch_repo: Arc<BatchRepo>,
+    storage: Arc<dyn StorageBackend>,
 }
 
 impl DownloadQueue {
     pub fn new(
         cfg: Arc<DownloadQueueCfg>,
-        publisher: Arc<Publisher>,
-        download_repo: Arc<DownloadRepo>,
-        storage_downloader: Arc<S3StorageDownloader>,
+            download_repo: Arc<DownloadRepo>,
+        batch_repo: Arc<BatchRepo>,
+        storage: Arc<dyn StorageBackend>,
     ) -> Self {
-        let config = cfg.clone();
-        task::spawn(
-            async move { tokio::fs::remove_dir_all(&config.storage_path).await },
-        );
-
         Self {
             cfg,
-            downloads: Arc::new(Mutex::new(HashMap::new())),
-            publisher,
             download_repo,
-            storage_downloader,
+            batch_repo,
+            storage,
         }
     }
 
     pub fn new_from_env(
-        publisher: Arc<Publisher>,
-        download_repo: Arc<DownloadRepo>,
-        storage_downloader: Arc<S3StorageDownloader>,
+            download_repo: Arc<DownloadRepo>,
+        batch_repo: Arc<BatchRepo>,
+        storage: Arc<dyn StorageBackend>,
     ) -> Result<Self, Box<dyn Error>> {
         let download_queue_cfg = Arc::new(DownloadQueueCfg::init_from_env()?);
 
         Ok(Self::new(
             download_queue_cfg,
-            publisher,
             download_repo,
-            storage_downloader,
+            batch_repo,
+            storage,
         ))
     }
 
-    pub async fn add(&self, link: &'_ str, requester_id: String) -> Result<String, Box<dyn Error>> {
-        let download = Download {
+    pub async fn add(
+        &self,
+        link: &'_ str,
+        requester_id: String,
+        options: DownloadOptions,
+    ) -> Result<QueuedDownload, Box<dyn Error>> {
+        let canonical_link = canonicalize_link(link);
+        let existing = self
+            .find_existing(link, canonical_link.as_str(), requester_id.clone(), &options)
+            .await?;
+        if let Some(download) = existing {
+            return self.add_existing(download).await.map(QueuedDownload::Download);
+        }
+
+        let media_info = match self.probe(link).await {
+            Ok(media_info) => media_info,
+            Err(e) => {
+                eprintln!("failed to probe metadata for {}: {}", link, e);
+                None
+            }
+        };
+
+        match media_info {
+            Some(media_info) if media_info.is_playlist() => self
+                .add_batch(link, requester_id, options, media_info)
+                .await
+                .map(QueuedDownload::Batch),
+            media_info => self
+                .create_download(new_download(link, canonical_link, requester_id, options, media_info.map(to_metadata)))
+                .await
+                .map(QueuedDownload::Download),
+        }
+    }
+
+    async fn add_batch(
+        &self,
+        link: &'_ str,
+        requester_id: String,
+        options: DownloadOptions,
+        media_info: MediaInfo,
+    ) -> Result<String, Box<dyn Error>> {
+        let batch = DownloadBatch {
             id: None,
-            state: DownloadState::Initiated,
             link: link.to_string(),
-            file: None,
+            title: media_info.title,
             insert_time: Some(Utc::now()),
-            percentage: 0,
-            requester_id: Some(requester_id),
+            requester_id: Some(requester_id.clone()),
+            downloads: vec![],
         };
 
+        let mut downloads = vec![];
+        for entry in self.playlist_videos(media_info.entries).await {
+            let entry_link = match &entry.url {
+                Some(url) => url.clone(),
+                None => {
+                    eprintln!("skipping playlist entry without url: {}", entry.id);
+                    continue;
+                }
+            };
+
+            let canonical_link = canonicalize_link(entry_link.as_str());
+            let download = match self
+                .find_existing(entry_link.as_str(), canonical_link.as_str(), requester_id.clone(), &options)
+                .await?
+            {
+                Some(download) => download,
+                None => new_download(entry_link.as_str(), canonical_link, requester_id.clone(), options.clone(), Some(entry_to_metadata(entry))),
+            };
+            downloads.push(download);
+        }
+        let requested = downloads.len();
+
+        // reused downloads are already done, only the new ones are published for the workers
+        let batch = self
+            .batch_repo
+            .add_batch_with_downloads(&batch, downloads, |d| match d.state {
+                DownloadState::Initiated => download_message(d).map(Some),
+                _ => Ok(None),
+            })
+            .await?;
+        darklight_metrics::DOWNLOADS.with_label_values(&["requested"]).inc_by(requested as u64);
+
+        for download in batch.downloads.iter().filter(|d| d.state.is_terminal()) {
+            self.verify_reused(download).await?;
+        }
+
+        batch.id.ok_or_else(|| "batch was not created properly".into())
+    }
+
+    /// Collects the videos of a playlist. Channels list their tabs as nested playlists, which are
+    /// probed for their videos in turn. Playlists nested any deeper are skipped.
+    async fn playlist_videos(&self, entries: Vec<PlaylistEntry>) -> Vec<PlaylistEntry> {
+        let (mut videos, nested) = flatten_entries(entries, 1);
+        for playlist in nested {
+            let url = match &playlist.url {
+                Some(url) => url.clone(),
+                None => {
+                    eprintln!("skipping nested playlist without url: {}", playlist.id);
+                    continue;
+                }
+            };
+
+            match self.probe(url.as_str()).await {
+                Ok(Some(media_info)) => {
+                    let (playlist_videos, skipped) = flatten_entries(media_info.entries, 0);
+                    videos.extend(playlist_videos);
+                    for entry in skipped {
+                        eprintln!("skipping playlist nested too deep: {}", entry.id);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!("failed to probe nested playlist {}: {}", url, e),
+            }
+        }
+
+        // a video may be listed on several tabs of a channel
+        let mut seen = HashSet::new();
+        videos.retain(|v| seen.insert(v.id.clone()));
+        videos
+    }
+
+    /// Builds a finished download sharing the stored file of an earlier download of the same media,
+    /// or returns `None` if there is nothing to reuse.
+    async fn find_existing(
+        &self,
+        link: &'_ str,
+        canonical_link: &'_ str,
+        requester_id: String,
+        options: &DownloadOptions,
+    ) -> Result<Option<Download>, Box<dyn Error>> {
+        let existing = match self
+            .download_repo
+            .get_completed_by_canonical_link(canonical_link, options)
+            .await?
+        {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        let object_key = existing.object_key().ok_or("completed download has no file")?.to_string();
+        // checked again once the download references the file, see `verify_reused`
+        if !self.storage.exists(object_key.as_str()).await? {
+            return Ok(None);
+        }
+        println!("reusing file of {} for {}", existing.id.as_deref().unwrap_or_default(), link);
+
+        Ok(Some(Download {
+            state: DownloadState::Done,
+            link: link.to_string(),
+            file: existing.file,
+            insert_time: Some(Utc::now()),
+            percentage: 100,
+            requester_id: Some(requester_id),
+            metadata: existing.metadata,
+            options: options.clone(),
+            file_size: existing.file_size,
+            storage_key: Some(object_key),
+            checksum: existing.checksum,
+            canonical_link: Some(canonical_link.to_string()),
+            ..Default::default()
+        }))
+    }
+
+    async fn add_existing(&self, download: Download) -> Result<String, Box<dyn Error>> {
         let download = self.download_repo.add_download(&download).await?;
-        self.publisher.publish(events::DOWNLOADS, &download).await?;
+        darklight_metrics::DOWNLOADS.with_label_values(&["requested"]).inc();
+        self.verify_reused(&download).await?;
+
+        download.id.ok_or_else(|| "download was not created properly".into())
+    }
+
+    /// The retention job may have deleted the reused file before the download referenced it, in
+    /// which case the download is downloaded after all. Checked under the object lock, which the
+    /// retention job holds while it deletes.
+    async fn verify_reused(&self, download: &Download) -> Result<(), Box<dyn Error>> {
+        let download_id = download.id.as_deref().ok_or("download was not created properly")?;
+        let object_key = download.object_key().ok_or("reused download has no file")?;
+
+        let lock = self.download_repo.lock_object(object_key).await?;
+        let stored = self.storage.exists(object_key).await?;
+        lock.commit().await?;
+
+        if !stored {
+            println!("reused file is gone, downloading {} again", download_id);
+            self.download_repo
+                .requeue_download_with_outbox(download_id, download_message)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn create_download(&self, download: Download) -> Result<String, Box<dyn Error>> {
+        // published by the outbox relay once the download is committed
+        let download = self
+            .download_repo
+            .add_download_with_outbox(&download, download_message)
+            .await?;
+        darklight_metrics::DOWNLOADS.with_label_values(&["requested"]).inc();
 
         match download.id {
             None => Err("download was not created properly".into()),
@@ -83,97 +297,205 @@ impl DownloadQueue {
         }
     }
 
+    async fn probe(&self, link: &'_ str) -> Result<Option<MediaInfo>, Box<dyn Error>> {
+        let ytd = YoutubeDL::new(&PathBuf::from(&self.cfg.storage_path), vec![], link)?;
+        let timeout = std::time::Duration::from_secs(self.cfg.probe_timeout_secs);
+        let media_info = tokio::time::timeout(timeout, ytd.probe())
+            .await
+            .map_err(|_| format!("probing {} timed out", link))??;
+
+        Ok(media_info.into_iter().next())
+    }
+
     pub async fn get(&self, download_id: &'_ str) -> Result<Option<Download>, Box<dyn Error>> {
         self.download_repo.get_by_download_id(download_id).await
     }
 
+    pub async fn cancel(&self, download_id: &'_ str) -> Result<(), Box<dyn Error>> {
+        // published by the outbox relay once the download is cancelled, a worker may settle it first
+        let cancelled = self
+            .download_repo
+            .cancel_download_with_outbox(download_id, cancel_message)
+            .await?;
+        if cancelled.is_some() {
+            return Ok(());
+        }
+
+        match self.get(download_id).await? {
+            Some(d) => Err(format!("download is already {}", d.state.as_str()).into()),
+            None => Err("could not find download".into()),
+        }
+    }
+
+    pub async fn retry(&self, download_id: &'_ str) -> Result<(), Box<dyn Error>> {
+        let download = match self.get(download_id).await? {
+            Some(d) => d,
+            None => return Err("could not find download".into()),
+        };
+
+        if !download.state.is_terminal() {
+            return Err(format!("download is still {}", download.state.as_str()).into());
+        }
+
+        self.download_repo
+            .reset_download_with_outbox(download_id, download_message)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_history(&self, download_id: &'_ str) -> Result<Vec<DownloadRun>, Box<dyn Error>> {
+        self.download_repo.get_download_history(download_id).await
+    }
+
+    pub async fn get_batch(&self, batch_id: &'_ str) -> Result<Option<DownloadBatch>, Box<dyn Error>> {
+        let mut batch = match self.batch_repo.get_by_batch_id(batch_id).await? {
+            Some(b) => b,
+            None => return Ok(None),
+        };
+        batch.downloads = self.download_repo.get_downloads_by_batch(batch_id).await?;
+
+        Ok(Some(batch))
+    }
+
+    pub async fn get_file_info(
+        &self,
+        download_id: &'_ str,
+    ) -> Result<Option<(String, ObjectInfo)>, Box<dyn Error>> {
+        let download = self.get(download_id).await?;
+        let file = Self::get_stored_file(download)?;
+        let mut info = match self.storage.head(file.key.as_str()).await? {
+            Some(i) => i,
+            None => return Ok(None),
+        };
+        info.etag = file.etag().or(info.etag);
+        Ok(Some((file.name, info)))
+    }
+
     pub async fn get_file(
         &self,
         download_id: &'_ str,
-    ) -> Result<Option<(String, Vec<u8>)>, Box<dyn Error>> {
+        range: Option<ByteRange>,
+    ) -> Result<Option<(String, StoredObject)>, Box<dyn Error>> {
         let download = self.get(download_id).await?;
-        let file_name = Self::get_file_name(download)?;
-        let data = match self
-            .storage_downloader
-            .download_file(file_name.clone().as_str())
-            .await?
-        {
-            Some(d) => d,
+        let file = Self::get_stored_file(download)?;
+        let mut object = match self.storage.get_stream(file.key.as_str(), range).await? {
+            Some(o) => o,
             None => return Ok(None),
         };
-        Ok(Some((file_name.clone(), data)))
+        object.info.etag = file.etag().or(object.info.etag);
+
+        // only a complete read can be verified against the checksum
+        if let (None, Some(checksum)) = (range, file.checksum) {
+            object.reader = Box::pin(ChecksumReader::new(object.reader, checksum));
+        }
+        Ok(Some((file.name, object)))
     }
 
-    fn get_file_name(download: Option<Download>) -> Result<String, Box<dyn Error>> {
-        let file_name = match download {
-            Some(d) => match d.file {
-                Some(f) => f,
-                None => return Err("could not find file name".into()),
-            },
-            None => {
-                return Err("download is not in the correct state".into());
-            }
+    pub async fn pin(&self, download_id: &'_ str, pinned: bool) -> Result<(), Box<dyn Error>> {
+        if self.get(download_id).await?.is_none() {
+            return Err("could not find download".into());
+        }
+
+        self.download_repo.update_pinned(download_id, pinned).await
+    }
+
+    pub async fn share_link(
+        &self,
+        download_id: &'_ str,
+        ttl_secs: Option<u32>,
+    ) -> Result<ShareLink, Box<dyn Error>> {
+        let ttl_secs = ttl_secs.unwrap_or(self.cfg.share_link_default_ttl_secs);
+        if ttl_secs == 0 || ttl_secs > self.cfg.share_link_max_ttl_secs {
+            return Err(format!(
+                "ttl must be between 1 and {} seconds",
+                self.cfg.share_link_max_ttl_secs
+            )
+            .into());
+        }
+
+        let download = match self.get(download_id).await? {
+            Some(d) => d,
+            None => return Err("could not find download".into()),
         };
-        Ok(file_name)
-    }
-
-    pub async fn remove_old(&self) -> Result<(), Box<dyn Error>> {
-        println!("remove old files triggered");
-        let mut downloads = self.downloads.lock().await;
-
-        for download in downloads.clone().iter().map(|d| d.1) {
-            if is_older(download.insert_time.unwrap(), Utc::now()) {
-                println!("cleaning up for: {}", download.id.as_ref().unwrap());
-                match self.clean_up(download).await {
-                    Ok(_) => {
-                        println!("cleanup done for: {}", download.id.as_deref().unwrap());
-
-                        match downloads.remove(download.id.as_ref().unwrap().as_str()) {
-                            None => {
-                                println!("Could not fine download")
-                            }
-                            Some(_) => {
-                                println!("removed from db")
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        println!("cleanup failed: {:?}", e)
-                    }
-                }
-            }
+        if !matches!(download.state, DownloadState::Done) {
+            return Err("download is not finished".into());
         }
 
-        Ok(())
+        let file = Self::get_stored_file(Some(download))?;
+        let url = self
+            .storage
+            .presign_get(file.key.as_str(), file.name.as_str(), ttl_secs)
+            .await?;
+
+        Ok(ShareLink {
+            url,
+            expires_at: Utc::now() + Duration::seconds(ttl_secs as i64),
+        })
     }
 
-    async fn clean_up(&self, download: &Download) -> std::io::Result<()> {
-        tokio::fs::remove_dir_all(format!(
-            "{}/{}",
-            self.cfg.storage_path,
-            download.id.as_ref().unwrap()
-        ))
-        .await
+    fn get_stored_file(download: Option<Download>) -> Result<StoredFile, Box<dyn Error>> {
+        let download = match download {
+            Some(d) => d,
+            None => return Err("download is not in the correct state".into()),
+        };
+
+        let key = download.object_key().map(|k| k.to_string());
+        match (download.file, key) {
+            (Some(name), Some(key)) => Ok(StoredFile {
+                name,
+                key,
+                checksum: download.checksum,
+            }),
+            _ => Err("could not find file name".into()),
+        }
     }
 }
 
-fn is_older(created: DateTime<Utc>, now: DateTime<Utc>) -> bool {
-    created + chrono::Duration::minutes(5) < now
+fn new_download(
+    link: &'_ str,
+    canonical_link: String,
+    requester_id: String,
+    options: DownloadOptions,
+    metadata: Option<DownloadMetadata>,
+) -> Download {
+    Download {
+        state: DownloadState::Initiated,
+        link: link.to_string(),
+        insert_time: Some(Utc::now()),
+        requester_id: Some(requester_id),
+        metadata,
+        options,
+        canonical_link: Some(canonical_link),
+        ..Default::default()
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use chrono::Utc;
+/// The message which requests `download` from the workers.
+fn download_message(download: &Download) -> Result<OutboxMessage, Box<dyn Error>> {
+    let event = Event::new(download.clone());
+    Ok(OutboxMessage::new(event.subject(), event.encode()?))
+}
 
-    use darklight_app::download_queue::is_older;
+fn cancel_message(download: &Download) -> Result<OutboxMessage, Box<dyn Error>> {
+    let event = Event::new(DownloadCancel::new(download.id.as_deref().ok_or("download has no id")?));
+    Ok(OutboxMessage::new(event.subject(), event.encode()?))
+}
 
-    #[test]
-    fn datetime() {
-        let older = is_older(
-            Utc::now() - chrono::Duration::minutes(5) - chrono::Duration::seconds(1),
-            Utc::now(),
-        );
+fn to_metadata(media_info: MediaInfo) -> DownloadMetadata {
+    DownloadMetadata {
+        duration: media_info.duration_seconds(),
+        title: media_info.title,
+        uploader: media_info.uploader,
+        thumbnail: media_info.thumbnail,
+    }
+}
 
-        assert_eq!(older, true)
+fn entry_to_metadata(entry: PlaylistEntry) -> DownloadMetadata {
+    DownloadMetadata {
+        title: entry.title,
+        uploader: entry.uploader,
+        duration: entry.duration.map(|d| d.round() as u32),
+        thumbnail: None,
     }
 }
@@ -3,11 +3,16 @@ use std::future::Future;
 use std::path::PathBuf;
 use std::sync::Arc;
 
+use tokio_util::sync::CancellationToken;
+
 use darklight_core::download::Download;
-use darklight_events::events;
+use darklight_core::download_error::{DownloadError, DownloadErrorKind};
+use darklight_core::download_options::{DownloadOptions, FormatPreset};
+use darklight_events::envelope::Event;
+use darklight_events::event_bus::EventPublisher;
 use darklight_events::models::{DownloadFileNameAvailable, DownloadStatus};
-use darklight_events::publisher::Publisher;
-use darklight_ytd::youtube_dl::{Arg, YoutubeDL};
+use darklight_ytd::audio_extraction::AudioExtraction;
+use darklight_ytd::youtube_dl::{Arg, FailureKind, YoutubeDL, YoutubeDLError};
 
 use crate::envconfig::Envconfig;
 
@@ -19,48 +24,53 @@ pub struct FileDownloaderCfg {
 
 pub struct FileDownloader {
     pub cfg: Arc<FileDownloaderCfg>,
-    publisher: Arc<Publisher>,
+    publisher: Arc<dyn EventPublisher>,
 }
 
 impl FileDownloader {
-    pub fn new(cfg: Arc<FileDownloaderCfg>, publisher: Arc<Publisher>) -> Self {
+    pub fn new(cfg: Arc<FileDownloaderCfg>, publisher: Arc<dyn EventPublisher>) -> Self {
         Self {
             cfg,
             publisher,
         }
     }
 
-    pub fn new_from_env(publisher: Arc<Publisher>) -> Result<Self, Box<dyn Error>> {
+    pub fn new_from_env(publisher: Arc<dyn EventPublisher>) -> Result<Self, Box<dyn Error>> {
         let file_downloader_cfg = Arc::new(FileDownloaderCfg::init_from_env()?);
 
         Ok(Self::new(file_downloader_cfg, publisher))
     }
 
-    pub async fn download(&self, download: &Download) -> Result<String, Box<dyn Error>> {
+    pub async fn download(&self, download: &Download, correlation_id: &str, cancellation: CancellationToken) -> Result<String, DownloadError> {
         if let Err(e) = download_media(
             self.cfg.storage_path.to_string(),
             download.link.as_str(),
             download.id.as_ref().unwrap().as_str(),
+            &download.options,
+            cancellation,
             |percentage| {
                 async move {
-                    if let Err(e) = self.publisher.publish(events::DOWNLOAD_UPDATE, DownloadStatus::new(download.id.as_ref().unwrap().as_str(), percentage)).await {
+                    if let Err(e) = self.publisher.publish_event(&Event::correlated(correlation_id, DownloadStatus::new(download.id.as_ref().unwrap().as_str(), percentage))).await {
                         eprintln!("{}", e)
                     }
                 }
             },
             |file_name| {
                 async move {
-                    if let Err(e) = self.publisher.publish(events::DOWNLOAD_FILE_NAME_AVAILABLE, DownloadFileNameAvailable::new(download.id.as_ref().unwrap().as_str(), file_name)).await {
+                    if let Err(e) = self.publisher.publish_event(&Event::correlated(correlation_id, DownloadFileNameAvailable::new(download.id.as_ref().unwrap().as_str(), file_name))).await {
                         eprintln!("{}", e)
                     }
                 }
             },
         ).await {
             println!("{}", e);
-            return Err("failure".into());
+            return Err(to_download_error(e));
         }
 
-        let mut dir = tokio::fs::read_dir(format!("{}/{}", self.cfg.storage_path, download.id.as_ref().unwrap())).await.unwrap();
+        let mut dir = match tokio::fs::read_dir(format!("{}/{}", self.cfg.storage_path, download.id.as_ref().unwrap())).await {
+            Ok(dir) => dir,
+            Err(e) => return Err(DownloadError::new(DownloadErrorKind::Unknown, e.to_string())),
+        };
 
         let file_name = dir.next_entry().await.map(|entry| entry.map(|e| e.file_name().to_string_lossy().to_string()));
 
@@ -68,44 +78,73 @@ impl FileDownloader {
             println!("downloaded: {}", f);
             Ok(f)
         } else {
-            Err("could not download file".into())
+            Err(DownloadError::new(DownloadErrorKind::Unknown, "could not download file"))
         }
     }
 
 
-    pub async fn get_file(&self, download_id: &'_ str) -> Result<Vec<u8>, Box<dyn Error>> {
+    pub async fn get_file_path(&self, download_id: &'_ str) -> Result<PathBuf, Box<dyn Error>> {
         let mut dir = tokio::fs::read_dir(format!("{}/{}", self.cfg.storage_path, download_id)).await?;
 
         if let Some(entry) = dir.next_entry().await? {
-            return FileDownloader::read_file(entry.path()).await;
+            return Ok(entry.path());
         }
 
         Err("could not find file".into())
     }
 
-    async fn read_file(file_path: PathBuf) -> Result<Vec<u8>, Box<dyn Error>> {
-        match tokio::fs::read(file_path).await {
-            Ok(f) => Ok(f),
-            Err(e) => Err(e.into())
+    /// Returns the ids of all downloads with a working directory, finished or not.
+    pub async fn list_download_dirs(&self) -> std::io::Result<Vec<String>> {
+        let mut dir = match tokio::fs::read_dir(&self.cfg.storage_path).await {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+
+        let mut download_ids = Vec::new();
+        while let Some(entry) = dir.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                download_ids.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+
+        Ok(download_ids)
+    }
+
+    pub async fn clean_up(&self, download_id: &'_ str) -> std::io::Result<()> {
+        match tokio::fs::remove_dir_all(format!("{}/{}", self.cfg.storage_path, download_id)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
         }
     }
 }
 
-async fn download_media<F, Fut, FAvailable, FutAvailable>(storage_path: String, link: &'_ str, id: &'_ str, progress_update_fn: F, file_name_available: FAvailable) -> Result<(), Box<dyn Error>>
+async fn download_media<F, Fut, FAvailable, FutAvailable>(storage_path: String, link: &'_ str, id: &'_ str, options: &DownloadOptions, cancellation: CancellationToken, progress_update_fn: F, file_name_available: FAvailable) -> Result<(), YoutubeDLError>
     where
         F: Fn(u32) -> Fut,
         FAvailable: Fn(String) -> FutAvailable,
         Fut: Future<Output=()>,
         FutAvailable: Future<Output=()> {
-    let args = vec![
+    // there is no point in fetching a video stream only to throw it away again
+    let format = match (&options.audio, &options.format) {
+        (Some(_), FormatPreset::BestVideo) => &FormatPreset::AudioOnly,
+        (_, format) => format,
+    };
+
+    let mut args = vec![
 //Arg::new("--quiet"),
 Arg::new("--progress"),
 Arg::new("--newline"),
+Arg::new("--no-playlist"),
 Arg::new_with_args("--output", "%(title).90s.%(ext)s"),
     ];
+    args.extend(format_args(format));
 
-    let path = PathBuf::from(format!("{storage_path}/{id}"));
-    let ytd = YoutubeDL::new(&path, args, link)?;
+    let mut ytd = YoutubeDL::new(&PathBuf::from(format!("{storage_path}/{id}")), args, link)?
+        .with_cancellation(cancellation);
+    if let Some(audio) = &options.audio {
+        ytd = ytd.extract_audio(&AudioExtraction::new(audio.codec, audio.bitrate));
+    }
 
 // start download
     let download = ytd.download(progress_update_fn, file_name_available).await?;
@@ -115,3 +154,35 @@ Arg::new_with_args("--output", "%(title).90s.%(ext)s"),
     Ok(())
 }
 
+fn format_args(format: &FormatPreset) -> Vec<Arg> {
+    match format {
+        FormatPreset::BestVideo => vec![Arg::new_with_args("--format", "bv*+ba/b")],
+        FormatPreset::Max720p => vec![Arg::new_with_args("--format", "bv*[height<=720]+ba/b[height<=720]")],
+        FormatPreset::AudioOnly => vec![Arg::new_with_args("--format", "ba/b")],
+        FormatPreset::FormatId(id) => vec![Arg::new_with_args("--format", id.as_str())],
+    }
+}
+
+fn to_download_error(e: YoutubeDLError) -> DownloadError {
+    let kind = match e.failure_kind() {
+        FailureKind::Cancelled => DownloadErrorKind::Cancelled,
+        FailureKind::UnsupportedUrl => DownloadErrorKind::UnsupportedUrl,
+        FailureKind::GeoBlocked => DownloadErrorKind::GeoBlocked,
+        FailureKind::PrivateVideo => DownloadErrorKind::PrivateVideo,
+        FailureKind::BotCheck => DownloadErrorKind::BotCheck,
+        FailureKind::Network => DownloadErrorKind::Network,
+        FailureKind::Unknown => DownloadErrorKind::Unknown,
+    };
+
+    let message = match e {
+        YoutubeDLError::Failure(stderr) => stderr
+            .lines()
+            .rev()
+            .find(|l| l.starts_with("ERROR:"))
+            .unwrap_or(stderr.trim())
+            .to_string(),
+        e => e.to_string(),
+    };
+
+    DownloadError::new(kind, message)
+}
@@ -3,6 +3,7 @@ extern crate envconfig_derive;
 
 pub mod download_queue;
 pub mod file_downloader;
+pub mod worker_pool;
 
 #[cfg(test)]
 mod tests {
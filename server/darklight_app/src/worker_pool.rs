@@ -0,0 +1,270 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use darklight_core::canonical_link::link_domain;
+
+use crate::envconfig::Envconfig;
+
+const UNKNOWN_DOMAIN: &str = "unknown";
+
+#[derive(Envconfig)]
+pub struct WorkerPoolCfg {
+    /// Workers consuming downloads in this process
+    #[envconfig(from = "WORKER_COUNT", default = "1")]
+    pub workers: usize,
+
+    /// Download messages a single worker holds at once, including the ones waiting for a slot
+    #[envconfig(from = "WORKER_MAX_IN_FLIGHT", default = "4")]
+    pub max_in_flight: usize,
+
+    /// yt-dlp processes running at once across all workers of this process
+    #[envconfig(from = "WORKER_MAX_DOWNLOADS", default = "2")]
+    pub max_downloads: usize,
+
+    /// yt-dlp processes running at once against the same domain
+    #[envconfig(from = "WORKER_MAX_DOWNLOADS_PER_DOMAIN", default = "2")]
+    pub max_downloads_per_domain: usize,
+}
+
+/// Limits how many downloads the workers of this process run at once.
+pub struct WorkerPool {
+    cfg: WorkerPoolCfg,
+    downloads: Arc<Semaphore>,
+    domains: Mutex<HashMap<String, DomainSlots>>,
+    in_flight: AtomicUsize,
+}
+
+struct DomainSlots {
+    semaphore: Arc<Semaphore>,
+    running: usize,
+    /// Slots still waiting for their permits, the domain is kept until they are dropped
+    waiting: usize,
+}
+
+/// A snapshot of how busy the workers of this process are.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Occupancy {
+    pub workers: usize,
+    pub in_flight: usize,
+    pub max_in_flight: usize,
+    pub running: usize,
+    pub max_downloads: usize,
+    /// Running downloads by domain
+    pub domains: BTreeMap<String, usize>,
+}
+
+impl WorkerPool {
+    pub fn new(cfg: WorkerPoolCfg) -> Self {
+        let cfg = WorkerPoolCfg {
+            workers: cfg.workers.max(1),
+            max_in_flight: cfg.max_in_flight.max(1),
+            max_downloads: cfg.max_downloads.max(1),
+            max_downloads_per_domain: cfg.max_downloads_per_domain.max(1),
+        };
+
+        Self {
+            downloads: Arc::new(Semaphore::new(cfg.max_downloads)),
+            domains: Mutex::new(HashMap::new()),
+            in_flight: AtomicUsize::new(0),
+            cfg,
+        }
+    }
+
+    pub fn new_from_env() -> Result<Self, Box<dyn Error>> {
+        let worker_pool_cfg = WorkerPoolCfg::init_from_env()?;
+        Ok(Self::new(worker_pool_cfg))
+    }
+
+    pub fn workers(&self) -> usize {
+        self.cfg.workers
+    }
+
+    pub fn max_in_flight(&self) -> usize {
+        self.cfg.max_in_flight
+    }
+
+    /// Counts a download message as in flight until the returned guard is dropped.
+    pub fn hold_message(self: &Arc<Self>) -> HeldMessage {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        HeldMessage { pool: self.clone() }
+    }
+
+    /// Waits until a download of `link` may run. The slot is given back once it is dropped.
+    pub async fn acquire(self: &Arc<Self>, link: &str) -> DownloadSlot {
+        let domain = link_domain(link).unwrap_or_else(|| UNKNOWN_DOMAIN.to_string());
+        let domain_semaphore = {
+            let mut domains = self.domains.lock().unwrap();
+            let slots = domains.entry(domain.to_string()).or_insert_with(|| DomainSlots {
+                semaphore: Arc::new(Semaphore::new(self.cfg.max_downloads_per_domain)),
+                running: 0,
+                waiting: 0,
+            });
+            slots.waiting += 1;
+            slots.semaphore.clone()
+        };
+        // created before waiting, so the domain is given back when the caller stops waiting
+        let mut slot = DownloadSlot {
+            pool: self.clone(),
+            domain,
+            permits: None,
+        };
+
+        // the domain comes first, so downloads waiting on a busy domain don't block other domains
+        let domain_permit = domain_semaphore.acquire_owned().await.expect("domain semaphore is never closed");
+        let download_permit = self.downloads.clone().acquire_owned().await.expect("download semaphore is never closed");
+
+        let mut domains = self.domains.lock().unwrap();
+        if let Some(slots) = domains.get_mut(slot.domain.as_str()) {
+            slots.waiting -= 1;
+            slots.running += 1;
+        }
+        slot.permits = Some((domain_permit, download_permit));
+        drop(domains);
+
+        slot
+    }
+
+    pub fn occupancy(&self) -> Occupancy {
+        let domains = self.domains.lock().unwrap()
+            .iter()
+            .filter(|(_, slots)| slots.running > 0)
+            .map(|(domain, slots)| (domain.to_string(), slots.running))
+            .collect::<BTreeMap<String, usize>>();
+
+        Occupancy {
+            workers: self.cfg.workers,
+            in_flight: self.in_flight.load(Ordering::SeqCst),
+            max_in_flight: self.cfg.workers * self.cfg.max_in_flight,
+            running: domains.values().sum(),
+            max_downloads: self.cfg.max_downloads,
+            domains,
+        }
+    }
+}
+
+pub struct HeldMessage {
+    pool: Arc<WorkerPool>,
+}
+
+impl Drop for HeldMessage {
+    fn drop(&mut self) {
+        self.pool.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub struct DownloadSlot {
+    pool: Arc<WorkerPool>,
+    domain: String,
+    permits: Option<(OwnedSemaphorePermit, OwnedSemaphorePermit)>,
+}
+
+impl Drop for DownloadSlot {
+    fn drop(&mut self) {
+        let mut domains = self.pool.domains.lock().unwrap();
+        // given back under the lock, so no other slot can pick up the semaphore before it is evicted
+        let permits = self.permits.take();
+
+        if let Some(slots) = domains.get_mut(self.domain.as_str()) {
+            match permits {
+                Some(_) => slots.running -= 1,
+                None => slots.waiting -= 1,
+            }
+            if slots.running == 0 && slots.waiting == 0 {
+                domains.remove(self.domain.as_str());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::worker_pool::{WorkerPool, WorkerPoolCfg};
+
+    fn pool(max_downloads: usize, max_downloads_per_domain: usize) -> Arc<WorkerPool> {
+        Arc::new(WorkerPool::new(WorkerPoolCfg {
+            workers: 2,
+            max_in_flight: 3,
+            max_downloads,
+            max_downloads_per_domain,
+        }))
+    }
+
+    async fn acquires_in_time(pool: &Arc<WorkerPool>, link: &str) -> bool {
+        tokio::time::timeout(Duration::from_millis(50), pool.acquire(link)).await.is_ok()
+    }
+
+    #[tokio::test]
+    async fn test_limits_downloads_per_domain() {
+        let pool = pool(3, 1);
+
+        let _youtube = pool.acquire("https://www.youtube.com/watch?v=tv8-4bn1Lr8").await;
+
+        assert!(!acquires_in_time(&pool, "https://youtu.be/other").await);
+        assert!(acquires_in_time(&pool, "https://vimeo.com/76979871").await);
+    }
+
+    #[tokio::test]
+    async fn test_limits_downloads_across_domains() {
+        let pool = pool(1, 1);
+
+        let youtube = pool.acquire("https://www.youtube.com/watch?v=tv8-4bn1Lr8").await;
+        assert!(!acquires_in_time(&pool, "https://vimeo.com/76979871").await);
+
+        drop(youtube);
+        assert!(acquires_in_time(&pool, "https://vimeo.com/76979871").await);
+    }
+
+    #[tokio::test]
+    async fn test_occupancy() {
+        let pool = pool(2, 2);
+
+        let _message = pool.hold_message();
+        let slot = pool.acquire("https://youtu.be/tv8-4bn1Lr8").await;
+        let occupancy = pool.occupancy();
+
+        assert_eq!(occupancy.in_flight, 1);
+        assert_eq!(occupancy.max_in_flight, 6);
+        assert_eq!(occupancy.running, 1);
+        assert_eq!(occupancy.domains.get("youtube.com"), Some(&1));
+
+        drop(slot);
+        assert!(pool.occupancy().domains.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_evicts_idle_domains() {
+        let pool = pool(2, 1);
+
+        let first = pool.acquire("https://youtu.be/tv8-4bn1Lr8").await;
+        let waiting = tokio::spawn({
+            let pool = pool.clone();
+            async move { drop(pool.acquire("https://youtu.be/other").await) }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        drop(first);
+        assert_eq!(pool.domains.lock().unwrap().len(), 1);
+
+        waiting.await.unwrap();
+        assert!(pool.domains.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_evicts_domains_given_up_on() {
+        let pool = pool(1, 1);
+
+        let youtube = pool.acquire("https://youtu.be/tv8-4bn1Lr8").await;
+        assert!(!acquires_in_time(&pool, "https://vimeo.com/76979871").await);
+        drop(youtube);
+
+        assert!(pool.domains.lock().unwrap().is_empty());
+    }
+}
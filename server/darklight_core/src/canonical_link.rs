@@ -0,0 +1,124 @@
+use url::Url;
+
+// query parameters which only track where a link was shared from
+const TRACKING_PARAMS: [&str; 8] = ["fbclid", "gclid", "igshid", "si", "feature", "ref", "ref_src", "pp"];
+
+/// Normalizes a link so the same media requested through different urls is only downloaded once.
+/// Links which cannot be parsed are returned trimmed but otherwise untouched.
+pub fn canonicalize_link(link: &str) -> String {
+    let mut url = match Url::parse(link.trim()) {
+        Ok(url) => url,
+        Err(_) => return link.trim().to_string(),
+    };
+    url.set_fragment(None);
+
+    if let Some(youtube_link) = canonicalize_youtube_link(&url) {
+        return youtube_link;
+    }
+
+    let mut params = url
+        .query_pairs()
+        .filter(|(key, _)| !is_tracking_param(key))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect::<Vec<(String, String)>>();
+    params.sort();
+
+    if params.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(params);
+    }
+
+    url.to_string()
+}
+
+/// The domain a link is downloaded from, with the hosts of one site folded into the same domain.
+pub fn link_domain(link: &str) -> Option<String> {
+    let url = Url::parse(link.trim()).ok()?;
+
+    match strip_host_prefix(url.host_str()?) {
+        "youtu.be" => Some("youtube.com".to_string()),
+        host => Some(host.to_string()),
+    }
+}
+
+fn strip_host_prefix(host: &str) -> &str {
+    host.strip_prefix("www.")
+        .or_else(|| host.strip_prefix("m."))
+        .or_else(|| host.strip_prefix("music."))
+        .unwrap_or(host)
+}
+
+fn is_tracking_param(key: &str) -> bool {
+    key.starts_with("utm_") || TRACKING_PARAMS.contains(&key)
+}
+
+fn canonicalize_youtube_link(url: &Url) -> Option<String> {
+    let host = strip_host_prefix(url.host_str()?);
+    let segments = url.path_segments()?.collect::<Vec<&str>>();
+    let param = |name: &str| url.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.into_owned());
+
+    let video_id = match (host, segments.as_slice()) {
+        ("youtu.be", [id, ..]) if !id.is_empty() => id.to_string(),
+        ("youtube.com", ["watch"]) => param("v")?,
+        ("youtube.com", ["shorts" | "embed" | "live", id, ..]) if !id.is_empty() => id.to_string(),
+        _ => return None,
+    };
+
+    // a playlist reference changes what gets downloaded, so it has to be kept
+    match param("list") {
+        Some(list) => Some(format!("https://www.youtube.com/watch?v={}&list={}", video_id, list)),
+        None => Some(format!("https://www.youtube.com/watch?v={}", video_id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canonical_link::{canonicalize_link, link_domain};
+
+    #[test]
+    fn test_canonicalize_youtube_links() {
+        let canonical = "https://www.youtube.com/watch?v=tv8-4bn1Lr8";
+
+        for link in [
+            "https://www.youtube.com/watch?v=tv8-4bn1Lr8",
+            "https://youtube.com/watch?feature=share&v=tv8-4bn1Lr8#t=10",
+            "https://m.youtube.com/watch?v=tv8-4bn1Lr8&t=42s",
+            "https://youtu.be/tv8-4bn1Lr8?si=abcdef",
+            "http://www.youtube.com/shorts/tv8-4bn1Lr8",
+            " https://www.youtube.com/embed/tv8-4bn1Lr8 ",
+        ] {
+            assert_eq!(canonicalize_link(link), canonical, "{}", link);
+        }
+    }
+
+    #[test]
+    fn test_canonicalize_keeps_playlist() {
+        assert_eq!(
+            canonicalize_link("https://youtu.be/tv8-4bn1Lr8?list=PL0vfts4VzfNiI1BsIK5u7LpPaIDKMJIDN&index=2"),
+            "https://www.youtube.com/watch?v=tv8-4bn1Lr8&list=PL0vfts4VzfNiI1BsIK5u7LpPaIDKMJIDN"
+        );
+        assert_eq!(
+            canonicalize_link("https://www.youtube.com/playlist?list=PL0vfts4VzfNiI1BsIK5u7LpPaIDKMJIDN"),
+            "https://www.youtube.com/playlist?list=PL0vfts4VzfNiI1BsIK5u7LpPaIDKMJIDN"
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_other_links() {
+        assert_eq!(
+            canonicalize_link("https://Vimeo.com/76979871?utm_source=newsletter&b=2&a=1#comments"),
+            "https://vimeo.com/76979871?a=1&b=2"
+        );
+        assert_eq!(canonicalize_link("https://vimeo.com/76979871?fbclid=abc"), "https://vimeo.com/76979871");
+        assert_eq!(canonicalize_link("not a url"), "not a url");
+    }
+
+    #[test]
+    fn test_link_domain() {
+        assert_eq!(link_domain("https://youtu.be/tv8-4bn1Lr8").as_deref(), Some("youtube.com"));
+        assert_eq!(link_domain("https://music.youtube.com/watch?v=tv8-4bn1Lr8").as_deref(), Some("youtube.com"));
+        assert_eq!(link_domain("https://Vimeo.com/76979871").as_deref(), Some("vimeo.com"));
+        assert_eq!(link_domain("not a url"), None);
+    }
+}
@@ -1,9 +1,11 @@
-use chrono::{serde::ts_milliseconds_option, DateTime, Utc};
+use chrono::{serde::ts_milliseconds, serde::ts_milliseconds_option, DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::download_error::DownloadError;
+use crate::download_options::DownloadOptions;
 use crate::download_state::DownloadState;
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct Download {
     pub id: Option<String>,
     pub state: DownloadState,
@@ -13,4 +15,71 @@ pub struct Download {
     pub insert_time: Option<DateTime<Utc>>,
     pub percentage: u32,
     pub requester_id: Option<String>,
+    pub metadata: Option<DownloadMetadata>,
+    #[serde(default)]
+    pub options: DownloadOptions,
+    pub batch_id: Option<String>,
+    pub error: Option<DownloadError>,
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default)]
+    pub file_size: Option<u64>,
+    /// Pinned downloads are kept by the retention job regardless of age
+    #[serde(default)]
+    pub pinned: bool,
+    /// Normalized form of the link, identical downloads share the same stored file
+    #[serde(default)]
+    pub canonical_link: Option<String>,
+    /// Key of the stored file, derived from its content. `file` is only the name shown to users
+    #[serde(default)]
+    pub storage_key: Option<String>,
+    /// Hex encoded SHA-256 of the stored file
+    #[serde(default)]
+    pub checksum: Option<String>,
+    /// Last time a worker reported on the download, running downloads are stalled without updates
+    #[serde(default, with = "ts_milliseconds_option")]
+    pub update_time: Option<DateTime<Utc>>,
+}
+
+impl Download {
+    /// Downloads finished before files were stored by content are stored under their file name.
+    pub fn object_key(&self) -> Option<&str> {
+        self.storage_key.as_deref().or(self.file.as_deref())
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct DownloadMetadata {
+    pub title: Option<String>,
+    pub uploader: Option<String>,
+    pub duration: Option<u32>,
+    pub thumbnail: Option<String>,
+}
+
+/// A previous run of a download, archived when the download was retried.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DownloadRun {
+    pub state: DownloadState,
+    pub file: Option<String>,
+    pub percentage: u32,
+    pub attempts: u32,
+    pub error: Option<DownloadError>,
+    #[serde(with = "ts_milliseconds")]
+    pub insert_time: DateTime<Utc>,
+    #[serde(with = "ts_milliseconds")]
+    pub archived_time: DateTime<Utc>,
+    #[serde(default)]
+    pub file_size: Option<u64>,
+    /// Key of the file the run stored, which is kept until the download expires
+    #[serde(default)]
+    pub storage_key: Option<String>,
+    #[serde(default)]
+    pub checksum: Option<String>,
+}
+
+impl DownloadRun {
+    /// Runs archived before files were stored by content are stored under their file name.
+    pub fn object_key(&self) -> Option<&str> {
+        self.storage_key.as_deref().or(self.file.as_deref())
+    }
 }
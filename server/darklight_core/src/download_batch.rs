@@ -0,0 +1,83 @@
+use chrono::{serde::ts_milliseconds_option, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::download::Download;
+use crate::download_state::DownloadState;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DownloadBatch {
+    pub id: Option<String>,
+    pub link: String,
+    pub title: Option<String>,
+    #[serde(with = "ts_milliseconds_option")]
+    pub insert_time: Option<DateTime<Utc>>,
+    pub requester_id: Option<String>,
+    pub downloads: Vec<Download>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchProgress {
+    pub total: u32,
+    pub done: u32,
+    pub failed: u32,
+    pub percentage: u32,
+}
+
+impl DownloadBatch {
+    pub fn progress(&self) -> BatchProgress {
+        let total = self.downloads.len() as u32;
+        let done = self.count(|s| matches!(s, DownloadState::Done));
+        let failed = self.count(|s| matches!(s, DownloadState::Error));
+
+        // finished downloads count as complete even if the last update never arrived
+        let percentage = match total {
+            0 => 0,
+            _ => self
+                .downloads
+                .iter()
+                .map(|d| match d.state {
+                    DownloadState::Done | DownloadState::Error | DownloadState::Cancelled => 100,
+                    _ => d.percentage.min(100),
+                })
+                .sum::<u32>()
+                / total,
+        };
+
+        BatchProgress {
+            total,
+            done,
+            failed,
+            percentage,
+        }
+    }
+
+    fn count(&self, predicate: impl Fn(&DownloadState) -> bool) -> u32 {
+        self.downloads.iter().filter(|d| predicate(&d.state)).count() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::download_batch::{BatchProgress, DownloadBatch};
+    use crate::download_state::DownloadState;
+    use crate::test_util::DownloadBuilder;
+
+    #[test]
+    fn test_batch_progress() {
+        let batch = DownloadBatch {
+            id: None,
+            link: "https://www.youtube.com/playlist?list=PL0vfts4VzfNiI1BsIK5u7LpPaIDKMJIDN".into(),
+            title: None,
+            insert_time: None,
+            requester_id: None,
+            downloads: vec![
+                DownloadBuilder::new().state(DownloadState::Done).percentage(95).build(),
+                DownloadBuilder::new().state(DownloadState::Downloading).percentage(50).build(),
+                DownloadBuilder::new().state(DownloadState::Initiated).percentage(0).build(),
+                DownloadBuilder::new().state(DownloadState::Error).percentage(10).build(),
+            ],
+        };
+
+        assert_eq!(batch.progress(), BatchProgress { total: 4, done: 1, failed: 1, percentage: 62 });
+    }
+}
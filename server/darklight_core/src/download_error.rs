@@ -0,0 +1,79 @@
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DownloadErrorKind {
+    Cancelled,
+    UnsupportedUrl,
+    GeoBlocked,
+    PrivateVideo,
+    /// The site asked to sign in to prove the request is not automated, which usually passes
+    BotCheck,
+    Network,
+    UploadFailed,
+    /// The process running the download went away before it finished
+    Interrupted,
+    Unknown,
+}
+
+impl DownloadErrorKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            DownloadErrorKind::Cancelled => "cancelled",
+            DownloadErrorKind::UnsupportedUrl => "unsupported-url",
+            DownloadErrorKind::GeoBlocked => "geo-blocked",
+            DownloadErrorKind::PrivateVideo => "private-video",
+            DownloadErrorKind::BotCheck => "bot-check",
+            DownloadErrorKind::Network => "network",
+            DownloadErrorKind::UploadFailed => "upload-failed",
+            DownloadErrorKind::Interrupted => "interrupted",
+            DownloadErrorKind::Unknown => "unknown",
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, DownloadErrorKind::BotCheck | DownloadErrorKind::Network | DownloadErrorKind::UploadFailed)
+    }
+
+    pub fn from_string(s: &str) -> Option<Self> {
+        let kind = match s {
+            "cancelled" => DownloadErrorKind::Cancelled,
+            "unsupported-url" => DownloadErrorKind::UnsupportedUrl,
+            "geo-blocked" => DownloadErrorKind::GeoBlocked,
+            "private-video" => DownloadErrorKind::PrivateVideo,
+            "bot-check" => DownloadErrorKind::BotCheck,
+            "network" => DownloadErrorKind::Network,
+            "upload-failed" => DownloadErrorKind::UploadFailed,
+            "interrupted" => DownloadErrorKind::Interrupted,
+            "unknown" => DownloadErrorKind::Unknown,
+            _ => { return None; }
+        };
+
+        Some(kind)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DownloadError {
+    pub kind: DownloadErrorKind,
+    pub message: String,
+}
+
+impl DownloadError {
+    pub fn new(kind: DownloadErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for DownloadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.kind.as_str(), self.message)
+    }
+}
+
+impl std::error::Error for DownloadError {}
@@ -0,0 +1,135 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct DownloadOptions {
+    #[serde(default)]
+    pub format: FormatPreset,
+    #[serde(default)]
+    pub audio: Option<AudioExtraction>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AudioExtraction {
+    pub codec: AudioCodec,
+    pub bitrate: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioCodec {
+    Mp3,
+    Opus,
+    M4a,
+    Flac,
+}
+
+impl AudioCodec {
+    pub fn as_str(&self) -> &str {
+        match self {
+            AudioCodec::Mp3 => "mp3",
+            AudioCodec::Opus => "opus",
+            AudioCodec::M4a => "m4a",
+            AudioCodec::Flac => "flac",
+        }
+    }
+
+    pub fn from_string(s: &str) -> Option<Self> {
+        [AudioCodec::Mp3, AudioCodec::Opus, AudioCodec::M4a, AudioCodec::Flac]
+            .into_iter()
+            .find(|codec| codec.as_str().eq_ignore_ascii_case(s))
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum FormatPreset {
+    #[default]
+    BestVideo,
+    Max720p,
+    AudioOnly,
+    FormatId(String),
+}
+
+const FORMAT_ID_PREFIX: &str = "format:";
+
+impl Serialize for FormatPreset {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        serializer.serialize_str(self.to_string().as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for FormatPreset {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+        let s = String::deserialize(deserializer)?;
+        match FormatPreset::from_string(s.as_str()) {
+            Some(preset) => Ok(preset),
+            None => Err(de::Error::custom(format!("Invalid format preset '{}'", s))),
+        }
+    }
+}
+
+impl std::fmt::Display for FormatPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormatPreset::BestVideo => write!(f, "best-video"),
+            FormatPreset::Max720p => write!(f, "max-720p"),
+            FormatPreset::AudioOnly => write!(f, "audio-only"),
+            FormatPreset::FormatId(id) => write!(f, "{}{}", FORMAT_ID_PREFIX, id),
+        }
+    }
+}
+
+impl FormatPreset {
+    pub fn from_string(s: &str) -> Option<Self> {
+        let preset = match s {
+            _ if s.eq_ignore_ascii_case("best-video") => FormatPreset::BestVideo,
+            _ if s.eq_ignore_ascii_case("max-720p") => FormatPreset::Max720p,
+            _ if s.eq_ignore_ascii_case("audio-only") => FormatPreset::AudioOnly,
+            // only the prefix is case insensitive, format ids are passed to yt-dlp as they are
+            _ if s.get(..FORMAT_ID_PREFIX.len()).is_some_and(|p| p.eq_ignore_ascii_case(FORMAT_ID_PREFIX)) => {
+                match &s[FORMAT_ID_PREFIX.len()..] {
+                    "" => { return None; }
+                    id => FormatPreset::FormatId(id.to_string()),
+                }
+            }
+            _ => { return None; }
+        };
+
+        Some(preset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::download_options::{AudioCodec, FormatPreset};
+
+    #[test]
+    fn test_format_preset_round_trip() {
+        for preset in [
+            FormatPreset::BestVideo,
+            FormatPreset::Max720p,
+            FormatPreset::AudioOnly,
+            FormatPreset::FormatId("137".into()),
+        ] {
+            assert_eq!(FormatPreset::from_string(preset.to_string().as_str()), Some(preset));
+        }
+    }
+
+    #[test]
+    fn test_format_preset_prefix_ignores_case() {
+        assert_eq!(FormatPreset::from_string("Format:hls-720p"), Some(FormatPreset::FormatId("hls-720p".into())));
+        assert_eq!(FormatPreset::from_string("FORMAT:dash-VIDEO"), Some(FormatPreset::FormatId("dash-VIDEO".into())));
+    }
+
+    #[test]
+    fn test_format_preset_invalid() {
+        assert_eq!(FormatPreset::from_string("format:"), None);
+        assert_eq!(FormatPreset::from_string("4k"), None);
+    }
+
+    #[test]
+    fn test_audio_codec_ignores_case() {
+        assert_eq!(AudioCodec::from_string("MP3"), Some(AudioCodec::Mp3));
+        assert_eq!(AudioCodec::from_string("Opus"), Some(AudioCodec::Opus));
+        assert_eq!(AudioCodec::from_string("wav"), None);
+    }
+}
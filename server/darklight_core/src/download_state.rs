@@ -1,11 +1,14 @@
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub enum DownloadState {
+    #[default]
     Initiated,
     Downloading,
     Done,
     Error,
+    Cancelled,
+    Expired,
 }
 
 impl Serialize for DownloadState {
@@ -15,6 +18,8 @@ impl Serialize for DownloadState {
             DownloadState::Downloading => "downloading",
             DownloadState::Done => "done",
             DownloadState::Error => "error",
+            DownloadState::Cancelled => "cancelled",
+            DownloadState::Expired => "expired",
         })
     }
 }
@@ -27,6 +32,8 @@ impl<'de> Deserialize<'de> for DownloadState {
             "downloading" => DownloadState::Downloading,
             "done" => DownloadState::Done,
             "error" => DownloadState::Error,
+            "cancelled" => DownloadState::Cancelled,
+            "expired" => DownloadState::Expired,
             other => { return Err(de::Error::custom(format!("Invalid state '{}'", other))); }
         };
 
@@ -35,12 +42,18 @@ impl<'de> Deserialize<'de> for DownloadState {
 }
 
 impl DownloadState {
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, DownloadState::Done | DownloadState::Error | DownloadState::Cancelled | DownloadState::Expired)
+    }
+
     pub fn as_str(&self) -> &str {
         match self {
             DownloadState::Initiated => "initiated",
             DownloadState::Downloading => "downloading",
             DownloadState::Done => "done",
             DownloadState::Error => "error",
+            DownloadState::Cancelled => "cancelled",
+            DownloadState::Expired => "expired",
         }
     }
 
@@ -50,6 +63,8 @@ impl DownloadState {
             "downloading" => DownloadState::Downloading,
             "done" => DownloadState::Done,
             "error" => DownloadState::Error,
+            "cancelled" => DownloadState::Cancelled,
+            "expired" => DownloadState::Expired,
             _ => { return None; }
         };
 
@@ -1,2 +1,8 @@
+pub mod canonical_link;
 pub mod download;
-pub mod download_state;
\ No newline at end of file
+pub mod download_batch;
+pub mod download_error;
+pub mod download_options;
+pub mod download_state;
+pub mod share_link;#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
@@ -0,0 +1,10 @@
+use chrono::{serde::ts_milliseconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A time-limited link to a finished download, which can be handed out without exposing the service.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub url: String,
+    #[serde(with = "ts_milliseconds")]
+    pub expires_at: DateTime<Utc>,
+}
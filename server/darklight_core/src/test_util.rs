@@ -0,0 +1,77 @@
+use chrono::{DateTime, Utc};
+
+use crate::download::Download;
+use crate::download_state::DownloadState;
+
+/// Builds downloads for tests across the workspace, starting out from `Download::default()` of a
+/// test video. Enabled for other crates by the `test-util` feature.
+pub struct DownloadBuilder {
+    download: Download,
+}
+
+impl DownloadBuilder {
+    pub fn new() -> Self {
+        Self {
+            download: Download {
+                link: "https://www.youtube.com/watch?v=tv8-4bn1Lr8".into(),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn id(mut self, id: &str) -> Self {
+        self.download.id = Some(id.into());
+        self
+    }
+
+    pub fn link(mut self, link: &str) -> Self {
+        self.download.link = link.into();
+        self
+    }
+
+    pub fn state(mut self, state: DownloadState) -> Self {
+        self.download.state = state;
+        self
+    }
+
+    pub fn percentage(mut self, percentage: u32) -> Self {
+        self.download.percentage = percentage;
+        self
+    }
+
+    pub fn attempts(mut self, attempts: u32) -> Self {
+        self.download.attempts = attempts;
+        self
+    }
+
+    pub fn stored(mut self, file: &str, file_size: u64) -> Self {
+        self.download.file = Some(file.into());
+        self.download.file_size = Some(file_size);
+        self
+    }
+
+    pub fn pinned(mut self) -> Self {
+        self.download.pinned = true;
+        self
+    }
+
+    pub fn requested_at(mut self, time: DateTime<Utc>) -> Self {
+        self.download.insert_time = Some(time);
+        self
+    }
+
+    pub fn settled_at(mut self, time: DateTime<Utc>) -> Self {
+        self.download.update_time = Some(time);
+        self
+    }
+
+    pub fn build(self) -> Download {
+        self.download
+    }
+}
+
+impl Default for DownloadBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
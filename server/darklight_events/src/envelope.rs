@@ -0,0 +1,150 @@
+use std::error::Error;
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A payload which can travel over the event bus, tied to the subject it is published on.
+pub trait EventPayload: Serialize + DeserializeOwned + Send + 'static {
+    const SUBJECT: &'static str;
+    const VERSION: u32;
+
+    /// Turns a payload published with an older `version` into the current one.
+    ///
+    /// Version 0 is a bare payload published before events were wrapped in an envelope.
+    fn upgrade(version: u32, payload: serde_json::Value) -> Result<Self, serde_json::Error> {
+        let _ = version;
+        serde_json::from_value(payload)
+    }
+}
+
+/// The envelope every event is published in.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Event<T> {
+    pub id: String,
+    pub version: u32,
+    pub timestamp: DateTime<Utc>,
+    /// The id of the event which started the chain this event is part of.
+    pub correlation_id: String,
+    pub payload: T,
+}
+
+#[derive(Deserialize)]
+struct RawEvent {
+    id: String,
+    version: u32,
+    timestamp: DateTime<Utc>,
+    correlation_id: String,
+    payload: serde_json::Value,
+}
+
+impl<T: EventPayload> Event<T> {
+    /// Creates an event which starts a new chain.
+    pub fn new(payload: T) -> Self {
+        let id = Uuid::new_v4().to_string();
+
+        Self {
+            correlation_id: id.clone(),
+            id,
+            version: T::VERSION,
+            timestamp: Utc::now(),
+            payload,
+        }
+    }
+
+    /// Creates an event which is part of the chain identified by `correlation_id`.
+    pub fn correlated(correlation_id: &str, payload: T) -> Self {
+        Self {
+            correlation_id: correlation_id.to_string(),
+            ..Self::new(payload)
+        }
+    }
+
+    pub fn subject(&self) -> &'static str {
+        T::SUBJECT
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let value = serde_json::from_slice::<serde_json::Value>(bytes)?;
+
+        match serde_json::from_value::<RawEvent>(value.clone()) {
+            Ok(raw) => {
+                let payload = if raw.version < T::VERSION {
+                    T::upgrade(raw.version, raw.payload)?
+                } else {
+                    // newer versions only ever add fields, which are ignored
+                    serde_json::from_value(raw.payload)?
+                };
+
+                Ok(Self {
+                    id: raw.id,
+                    version: T::VERSION,
+                    timestamp: raw.timestamp,
+                    correlation_id: raw.correlation_id,
+                    payload,
+                })
+            }
+            Err(_) => Ok(Self::new(T::upgrade(0, value)?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::envelope::{Event, EventPayload};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Renamed {
+        name: String,
+    }
+
+    impl EventPayload for Renamed {
+        const SUBJECT: &'static str = "test.renamed";
+        const VERSION: u32 = 2;
+
+        fn upgrade(version: u32, mut payload: serde_json::Value) -> Result<Self, serde_json::Error> {
+            if version < 2 {
+                if let Some(title) = payload.get("title").cloned() {
+                    payload["name"] = title;
+                }
+            }
+            serde_json::from_value(payload)
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let event = Event::correlated("chain", Renamed { name: "a".to_string() });
+
+        let decoded = Event::<Renamed>::decode(&event.encode().unwrap()).unwrap();
+
+        assert_eq!(decoded.id, event.id);
+        assert_eq!(decoded.correlation_id, "chain");
+        assert_eq!(decoded.payload, event.payload);
+    }
+
+    #[test]
+    fn test_decode_upgrades_older_versions() {
+        let old = br#"{"id":"1","version":1,"timestamp":"2022-05-01T10:00:00Z","correlation_id":"1","payload":{"title":"a"}}"#;
+
+        let decoded = Event::<Renamed>::decode(old).unwrap();
+
+        assert_eq!(decoded.version, 2);
+        assert_eq!(decoded.payload, Renamed { name: "a".to_string() });
+    }
+
+    #[test]
+    fn test_decode_bare_payload() {
+        let decoded = Event::<Renamed>::decode(br#"{"title":"a"}"#).unwrap();
+
+        assert_eq!(decoded.payload, Renamed { name: "a".to_string() });
+        assert_eq!(decoded.correlation_id, decoded.id);
+    }
+}
@@ -0,0 +1,220 @@
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use futures::{future, stream, Stream, StreamExt};
+use tokio_util::sync::CancellationToken;
+
+use crate::envelope::{Event, EventPayload};
+
+/// A message received from the event bus, independent of the transport it arrived on.
+#[derive(Clone)]
+pub struct Message {
+    pub subject: String,
+    pub payload: Vec<u8>,
+    acker: Option<Arc<dyn Acknowledger>>,
+}
+
+/// Settles a message on transports with delivery guarantees.
+#[async_trait]
+pub trait Acknowledger: Send + Sync {
+    /// The message was handled and must not be delivered again.
+    async fn ack(&self) -> Result<(), Box<dyn Error>>;
+
+    /// Handling the message failed, it is delivered again until it runs out of deliveries.
+    async fn nak(&self) -> Result<(), Box<dyn Error>>;
+
+    /// The message can never be handled and is moved to the dead letter subject.
+    async fn dead_letter(&self, reason: &str) -> Result<(), Box<dyn Error>>;
+}
+
+impl Message {
+    pub fn new(subject: String, payload: Vec<u8>) -> Self {
+        Self { subject, payload, acker: None }
+    }
+
+    pub fn with_acker(subject: String, payload: Vec<u8>, acker: Arc<dyn Acknowledger>) -> Self {
+        Self { subject, payload, acker: Some(acker) }
+    }
+
+    pub async fn ack(&self) -> Result<(), Box<dyn Error>> {
+        match &self.acker {
+            Some(acker) => acker.ack().await,
+            None => Ok(()),
+        }
+    }
+
+    pub async fn nak(&self) -> Result<(), Box<dyn Error>> {
+        match &self.acker {
+            Some(acker) => acker.nak().await,
+            None => Ok(()),
+        }
+    }
+
+    pub async fn dead_letter(&self, reason: &str) -> Result<(), Box<dyn Error>> {
+        match &self.acker {
+            Some(acker) => acker.dead_letter(reason).await,
+            None => Ok(()),
+        }
+    }
+}
+
+pub type MessageStream = Pin<Box<dyn Stream<Item = Message> + Send>>;
+
+pub type EventStream<T> = Pin<Box<dyn Stream<Item = Event<T>> + Send>>;
+
+#[async_trait]
+pub trait EventPublisher: Send + Sync {
+    async fn publish_bytes(&self, subject: &str, payload: Vec<u8>) -> Result<(), Box<dyn Error>>;
+
+    /// Waits until everything published so far has been handed to the transport.
+    async fn flush(&self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Fails when the connection to the transport is lost.
+    async fn ping(&self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+pub trait EventSubscriber: Send + Sync {
+    /// Subscribes to `subject`. Subscribers sharing a `group` split the messages between them,
+    /// every other subscriber sees every message.
+    async fn subscribe(&self, subject: &str, group: Option<&str>) -> Result<MessageStream, Box<dyn Error>>;
+
+    /// Fails when the connection to the transport is lost.
+    async fn ping(&self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+impl dyn EventPublisher {
+    pub async fn publish_event<T: EventPayload>(&self, event: &Event<T>) -> Result<(), Box<dyn Error>> {
+        let payload = event.encode()?;
+
+        self.publish_bytes(T::SUBJECT, payload).await
+    }
+}
+
+impl dyn EventSubscriber {
+    /// Subscribes to the events of type `T`, skipping messages which can't be decoded.
+    pub async fn subscribe_event<T: EventPayload>(&self, group: Option<&str>) -> Result<EventStream<T>, Box<dyn Error>> {
+        let sub = self.subscribe(T::SUBJECT, group).await?;
+
+        Ok(Box::pin(sub.filter_map(|msg| {
+            future::ready(match Event::<T>::decode(&msg.payload) {
+                Ok(event) => Some(event),
+                Err(e) => {
+                    eprintln!("failed to decode event on {}: {}", msg.subject, e);
+                    None
+                }
+            })
+        })))
+    }
+
+    /// Runs `handler` for every event of type `T` until `stop` is cancelled. Events are acknowledged
+    /// once the handler succeeds, failed events are handed back to the transport to be delivered again.
+    pub async fn run_event<T, F, Fut>(&self, group: Option<&str>, stop: CancellationToken, handler: F) -> Result<(), Box<dyn Error>>
+        where
+            T: EventPayload,
+            F: Fn(Event<T>) -> Fut,
+            Fut: Future<Output=Result<(), Box<dyn Error>>>
+    {
+        self.run_event_concurrently(group, 1, stop, handler).await
+    }
+
+    /// Like `run_event`, but handles up to `max_in_flight` events at once. No further messages
+    /// are taken from the subscription while all of them are busy.
+    ///
+    /// Once `stop` is cancelled no new messages are taken, and the events being handled are
+    /// finished before returning.
+    pub async fn run_event_concurrently<T, F, Fut>(&self, group: Option<&str>, max_in_flight: usize, stop: CancellationToken, handler: F) -> Result<(), Box<dyn Error>>
+        where
+            T: EventPayload,
+            F: Fn(Event<T>) -> Fut,
+            Fut: Future<Output=Result<(), Box<dyn Error>>>
+    {
+        let sub = self.subscribe(T::SUBJECT, group).await?;
+
+        handle_messages(sub.take_until(async move { stop.cancelled().await }), group, max_in_flight, handler).await;
+
+        Ok(())
+    }
+
+    /// Like `run_event`, but once `stop` is cancelled the messages already delivered are handled
+    /// as well, so events published before stopping aren't left for another process.
+    pub async fn run_event_draining<T, F, Fut>(&self, group: Option<&str>, stop: CancellationToken, handler: F) -> Result<(), Box<dyn Error>>
+        where
+            T: EventPayload,
+            F: Fn(Event<T>) -> Fut,
+            Fut: Future<Output=Result<(), Box<dyn Error>>>
+    {
+        let sub = self.subscribe(T::SUBJECT, group).await?;
+
+        handle_messages(until_drained(sub, stop), group, 1, handler).await;
+
+        Ok(())
+    }
+}
+
+/// Ends once `stop` is cancelled and no further message is ready.
+fn until_drained(sub: MessageStream, stop: CancellationToken) -> impl Stream<Item=Message> {
+    stream::unfold((sub, stop), |(mut sub, stop)| async move {
+        let msg = tokio::select! {
+            biased;
+            msg = sub.next() => msg,
+            _ = stop.cancelled() => None,
+        };
+
+        msg.map(|msg| (msg, (sub, stop)))
+    })
+}
+
+async fn handle_messages<T, F, Fut>(messages: impl Stream<Item=Message>, group: Option<&str>, max_in_flight: usize, handler: F)
+    where
+        T: EventPayload,
+        F: Fn(Event<T>) -> Fut,
+        Fut: Future<Output=Result<(), Box<dyn Error>>>
+{
+    messages.for_each_concurrent(max_in_flight.max(1), |msg| {
+        let handler = &handler;
+        async move {
+            let decoded = Event::<T>::decode(&msg.payload).map_err(|e| e.to_string());
+            let event = match decoded {
+                Ok(event) => event,
+                Err(e) => {
+                    let reason = format!("failed to decode event: {}", e);
+                    eprintln!("{} on {}", reason, msg.subject);
+                    settle(msg.dead_letter(reason.as_str()).await);
+                    return;
+                }
+            };
+
+            let started = Instant::now();
+            let result = handler(event).await.map_err(|e| e.to_string());
+            let outcome = if result.is_ok() { "ok" } else { "error" };
+            darklight_metrics::HANDLER_DURATION
+                .with_label_values(&[msg.subject.as_str(), group.unwrap_or_default(), outcome])
+                .observe(started.elapsed().as_secs_f64());
+
+            match result {
+                Ok(()) => settle(msg.ack().await),
+                Err(e) => {
+                    eprintln!("failed to handle event on {}: {}", msg.subject, e);
+                    settle(msg.nak().await);
+                }
+            }
+        }
+    }).await;
+}
+
+fn settle(result: Result<(), Box<dyn Error>>) {
+    if let Err(e) = result {
+        eprintln!("failed to settle message: {}", e)
+    }
+}
@@ -1,11 +1,27 @@
+// Every subject below darklight
+pub const ALL: &str = "darklight.>";
+
 // Events
 pub const DOWNLOADS: &str = "darklight.downloads";
 pub const DOWNLOAD_DONE: &str = "darklight.downloading-done";
 pub const DOWNLOAD_UPDATE: &str = "darklight.download-update";
 pub const DOWNLOAD_FILE_NAME_AVAILABLE: &str = "darklight.download-file-name-update";
+pub const DOWNLOAD_FAILED: &str = "darklight.download-failed";
+pub const DOWNLOAD_ATTEMPT: &str = "darklight.download-attempt";
+pub const DOWNLOAD_CANCEL: &str = "darklight.download-cancel";
 
 // Groups
 pub const WORKER_GROUP: &str = "darklight.worker";
 pub const DONE_DOWNLOADING_GROUP: &str = "darklight.done-downloading";
 pub const DOWNLOAD_UPDATE_GROUP: &str = "darklight.update-download";
 pub const DOWNLOAD_FILE_NAME_AVAILABLE_GROUP: &str = "darklight.file-name-available";
+pub const DOWNLOAD_FAILED_GROUP: &str = "darklight.download-failed";
+pub const DOWNLOAD_ATTEMPT_GROUP: &str = "darklight.download-attempt";
+// Moves messages which ran out of deliveries to the dead letters, once per message
+pub const DEAD_LETTER_GROUP: &str = "darklight.dead-letter";
+
+// Connectivity checks, outside of ALL so they are never stored in a stream
+pub const PING: &str = "_darklight.ping";
+
+// Poison messages are republished below this subject, followed by their original subject
+pub const DEAD_LETTER: &str = "darklight.dead-letter";
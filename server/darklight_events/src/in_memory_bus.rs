@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use futures::stream;
+use tokio::sync::{broadcast, mpsc, Mutex as AsyncMutex};
+
+use crate::envconfig::Envconfig;
+use crate::event_bus::{EventPublisher, EventSubscriber, Message, MessageStream};
+
+#[derive(Envconfig)]
+pub struct InMemoryBusCfg {
+    /// How many messages a slow subscriber may fall behind before it starts missing messages.
+    /// Publishing waits for a slow queue group instead.
+    #[envconfig(from = "EVENT_BUS_CAPACITY", default = "1024")]
+    pub capacity: usize,
+}
+
+/// An event bus living inside the process, for single binary deployments and tests.
+///
+/// Plain subscribers are fed from a broadcast channel per subject. Queue groups share one bounded
+/// channel between their members, and every message is taken by whichever member is free next,
+/// like a NATS queue group.
+pub struct InMemoryBus {
+    cfg: Arc<InMemoryBusCfg>,
+    subjects: Mutex<HashMap<String, SubjectChannels>>,
+}
+
+struct SubjectChannels {
+    broadcast: broadcast::Sender<Message>,
+    groups: HashMap<String, GroupChannels>,
+}
+
+struct GroupChannels {
+    sender: mpsc::Sender<Message>,
+    receiver: Arc<AsyncMutex<mpsc::Receiver<Message>>>,
+}
+
+impl GroupChannels {
+    fn new(capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::channel(capacity);
+        Self {
+            sender,
+            receiver: Arc::new(AsyncMutex::new(receiver)),
+        }
+    }
+
+    /// Every member of the group holds on to the shared receiver.
+    fn has_members(&self) -> bool {
+        Arc::strong_count(&self.receiver) > 1
+    }
+}
+
+impl InMemoryBus {
+    pub fn new(cfg: Arc<InMemoryBusCfg>) -> Self {
+        Self {
+            cfg,
+            subjects: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn new_from_env() -> Result<Self, Box<dyn Error>> {
+        let cfg = Arc::new(InMemoryBusCfg::init_from_env()?);
+        Ok(Self::new(cfg))
+    }
+
+    fn with_subject<R>(&self, subject: &str, f: impl FnOnce(&mut SubjectChannels) -> R) -> R {
+        let mut subjects = self.subjects.lock().unwrap();
+        let channels = subjects
+            .entry(subject.to_string())
+            .or_insert_with(|| SubjectChannels {
+                broadcast: broadcast::channel(self.cfg.capacity).0,
+                groups: HashMap::new(),
+            });
+
+        f(channels)
+    }
+}
+
+#[async_trait]
+impl EventPublisher for InMemoryBus {
+    async fn publish_bytes(&self, subject: &str, payload: Vec<u8>) -> Result<(), Box<dyn Error>> {
+        let msg = Message::new(subject.to_string(), payload);
+
+        let groups = self.with_subject(subject, |channels| {
+            // an error only means nobody is subscribed outside of a group
+            let _ = channels.broadcast.send(msg.clone());
+
+            // nobody would take messages off the channel of a group without members
+            channels.groups.retain(|_, group| group.has_members());
+            channels.groups.values().map(|group| group.sender.clone()).collect::<Vec<_>>()
+        });
+
+        for group in groups {
+            // an error only means the last member left in the meantime
+            let _ = group.send(msg.clone()).await;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for InMemoryBus {
+    async fn subscribe(&self, subject: &str, group: Option<&str>) -> Result<MessageStream, Box<dyn Error>> {
+        if let Some(g) = group {
+            let capacity = self.cfg.capacity;
+            let rx = self.with_subject(subject, |channels| {
+                channels.groups
+                    .entry(g.to_string())
+                    .or_insert_with(|| GroupChannels::new(capacity))
+                    .receiver
+                    .clone()
+            });
+
+            return Ok(Box::pin(stream::unfold(rx, |rx| async move {
+                // only held while waiting, a member busy with a message leaves the next one to the others
+                let msg = rx.lock().await.recv().await;
+                msg.map(|msg| (msg, rx))
+            })));
+        }
+
+        let rx = self.with_subject(subject, |channels| channels.broadcast.subscribe());
+
+        Ok(Box::pin(stream::unfold(rx, |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(msg) => return Some((msg, rx)),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        eprintln!("in memory subscriber lagged behind, skipped {} messages", skipped)
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use futures::StreamExt;
+    use tokio_util::sync::CancellationToken;
+
+    use crate::envelope::Event;
+    use crate::event_bus::{EventPublisher, EventSubscriber, MessageStream};
+    use crate::in_memory_bus::{InMemoryBus, InMemoryBusCfg};
+    use crate::models::DownloadCancel;
+
+    fn bus() -> Arc<InMemoryBus> {
+        Arc::new(InMemoryBus::new(Arc::new(InMemoryBusCfg { capacity: 16 })))
+    }
+
+    async fn next_payload(stream: &mut MessageStream) -> Option<Vec<u8>> {
+        tokio::time::timeout(Duration::from_millis(50), stream.next())
+            .await
+            .ok()
+            .flatten()
+            .map(|msg| msg.payload)
+    }
+
+    #[tokio::test]
+    async fn test_every_plain_subscriber_receives_messages() {
+        let bus = bus();
+        let mut first = bus.subscribe("subject", None).await.unwrap();
+        let mut second = bus.subscribe("subject", None).await.unwrap();
+        let mut other = bus.subscribe("other", None).await.unwrap();
+
+        bus.publish_bytes("subject", b"payload".to_vec()).await.unwrap();
+
+        assert_eq!(next_payload(&mut first).await, Some(b"payload".to_vec()));
+        assert_eq!(next_payload(&mut second).await, Some(b"payload".to_vec()));
+        assert_eq!(next_payload(&mut other).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_group_members_split_messages() {
+        let bus = bus();
+        let mut first = bus.subscribe("subject", Some("group")).await.unwrap();
+        let mut second = bus.subscribe("subject", Some("group")).await.unwrap();
+        let mut other_group = bus.subscribe("subject", Some("other")).await.unwrap();
+
+        bus.publish_bytes("subject", b"1".to_vec()).await.unwrap();
+        bus.publish_bytes("subject", b"2".to_vec()).await.unwrap();
+
+        // whichever member asks first takes the next message
+        assert_eq!(next_payload(&mut second).await, Some(b"1".to_vec()));
+        assert_eq!(next_payload(&mut first).await, Some(b"2".to_vec()));
+        assert_eq!(next_payload(&mut first).await, None);
+        assert_eq!(next_payload(&mut second).await, None);
+        assert_eq!(next_payload(&mut other_group).await, Some(b"1".to_vec()));
+        assert_eq!(next_payload(&mut other_group).await, Some(b"2".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_group_member_is_skipped() {
+        let bus = bus();
+        let first = bus.subscribe("subject", Some("group")).await.unwrap();
+        let mut second = bus.subscribe("subject", Some("group")).await.unwrap();
+        drop(first);
+
+        bus.publish_bytes("subject", b"1".to_vec()).await.unwrap();
+
+        assert_eq!(next_payload(&mut second).await, Some(b"1".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_group_without_members_does_not_block() {
+        let bus = bus();
+        drop(bus.subscribe("subject", Some("group")).await.unwrap());
+
+        let publish = async {
+            for _ in 0..bus.cfg.capacity + 1 {
+                bus.publish_bytes("subject", b"1".to_vec()).await.unwrap();
+            }
+        };
+
+        assert!(tokio::time::timeout(Duration::from_millis(100), publish).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_typed_events_use_their_subject() {
+        let bus = bus();
+        let publisher: Arc<dyn EventPublisher> = bus.clone();
+        let subscriber: Arc<dyn EventSubscriber> = bus;
+        let mut events = subscriber.subscribe_event::<DownloadCancel>(None).await.unwrap();
+        let event = Event::new(DownloadCancel::new("some-id"));
+
+        publisher.publish_event(&event).await.unwrap();
+
+        let received = events.next().await.unwrap();
+        assert_eq!(received.id, event.id);
+        assert_eq!(received.payload.download_id, "some-id");
+    }
+
+    #[tokio::test]
+    async fn test_events_are_handled_concurrently() {
+        let bus = bus();
+        let publisher: Arc<dyn EventPublisher> = bus.clone();
+        let subscriber: Arc<dyn EventSubscriber> = bus;
+        // both handlers have to run at once to get past the barrier
+        let barrier = Arc::new(tokio::sync::Barrier::new(2));
+        let (done_tx, mut done_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            subscriber.run_event_concurrently(Some("group"), 2, CancellationToken::new(), |event: Event<DownloadCancel>| {
+                let barrier = barrier.clone();
+                let done_tx = done_tx.clone();
+                async move {
+                    barrier.wait().await;
+                    done_tx.send(event.payload.download_id).unwrap();
+                    Ok(())
+                }
+            }).await.unwrap();
+        });
+        tokio::task::yield_now().await;
+
+        publisher.publish_event(&Event::new(DownloadCancel::new("1"))).await.unwrap();
+        publisher.publish_event(&Event::new(DownloadCancel::new("2"))).await.unwrap();
+
+        for _ in 0..2 {
+            assert!(tokio::time::timeout(Duration::from_secs(1), done_rx.recv()).await.unwrap().is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stop_finishes_event_in_flight() {
+        let bus = bus();
+        let publisher: Arc<dyn EventPublisher> = bus.clone();
+        let subscriber: Arc<dyn EventSubscriber> = bus;
+        let stop = CancellationToken::new();
+        let (started_tx, mut started_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (done_tx, mut done_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let run = tokio::spawn({
+            let stop = stop.clone();
+            async move {
+                subscriber.run_event(None, stop, |event: Event<DownloadCancel>| {
+                    let started_tx = started_tx.clone();
+                    let done_tx = done_tx.clone();
+                    async move {
+                        started_tx.send(()).unwrap();
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        done_tx.send(event.payload.download_id).unwrap();
+                        Ok(())
+                    }
+                }).await.is_ok()
+            }
+        });
+        tokio::task::yield_now().await;
+
+        publisher.publish_event(&Event::new(DownloadCancel::new("1"))).await.unwrap();
+        started_rx.recv().await.unwrap();
+        stop.cancel();
+
+        assert!(tokio::time::timeout(Duration::from_secs(1), run).await.unwrap().unwrap());
+        assert_eq!(done_rx.try_recv().ok(), Some("1".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_draining_handles_delivered_events_after_stop() {
+        let bus = bus();
+        let publisher: Arc<dyn EventPublisher> = bus.clone();
+        let subscriber: Arc<dyn EventSubscriber> = bus;
+        let stop = CancellationToken::new();
+        let (done_tx, mut done_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let run = subscriber.run_event_draining(None, stop.clone(), |event: Event<DownloadCancel>| {
+            let done_tx = done_tx.clone();
+            async move {
+                done_tx.send(event.payload.download_id).unwrap();
+                Ok(())
+            }
+        });
+        tokio::pin!(run);
+        // subscribes, then waits for messages
+        assert!(tokio::time::timeout(Duration::from_millis(10), &mut run).await.is_err());
+
+        publisher.publish_event(&Event::new(DownloadCancel::new("1"))).await.unwrap();
+        publisher.publish_event(&Event::new(DownloadCancel::new("2"))).await.unwrap();
+        stop.cancel();
+
+        assert!(tokio::time::timeout(Duration::from_secs(1), run).await.unwrap().is_ok());
+        assert_eq!(done_rx.try_recv().ok(), Some("1".to_string()));
+        assert_eq!(done_rx.try_recv().ok(), Some("2".to_string()));
+    }
+}
@@ -0,0 +1,349 @@
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_nats::jetstream::consumer::{self, pull, AckPolicy, PullConsumer};
+use async_nats::connection::State;
+use async_nats::jetstream::{self, AckKind};
+use async_nats::HeaderMap;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::{future, StreamExt};
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+
+use crate::envconfig::Envconfig;
+use crate::event_bus::{Acknowledger, EventPublisher, EventSubscriber, Message, MessageStream};
+use crate::events;
+
+#[derive(Envconfig)]
+pub struct JetStreamCfg {
+    #[envconfig(from = "NATS_URL", default = "localhost:4222")]
+    pub nats_url: String,
+
+    #[envconfig(from = "JETSTREAM_STREAM", default = "darklight")]
+    pub stream: String,
+
+    /// How long events are kept in the stream
+    #[envconfig(from = "JETSTREAM_MAX_AGE_HOURS", default = "168")]
+    pub max_age_hours: u64,
+
+    /// How long a consumer may go quiet before a message is delivered again
+    #[envconfig(from = "JETSTREAM_ACK_WAIT_SECS", default = "30")]
+    pub ack_wait_secs: u64,
+
+    /// Deliveries of a message before it is moved to the dead letter subject
+    #[envconfig(from = "JETSTREAM_MAX_DELIVER", default = "5")]
+    pub max_deliver: i64,
+
+    #[envconfig(from = "JETSTREAM_REDELIVERY_DELAY_SECS", default = "10")]
+    pub redelivery_delay_secs: u64,
+}
+
+/// An event bus on top of NATS JetStream, so events survive restarts of every process.
+///
+/// Queue groups become durable pull consumers which only let go of a message once it is
+/// acknowledged. Subscribers outside of a group only see live events, like on core NATS.
+pub struct JetStreamBus {
+    cfg: Arc<JetStreamCfg>,
+    client: async_nats::Client,
+    context: jetstream::Context,
+    stream: jetstream::stream::Stream,
+}
+
+impl JetStreamBus {
+    pub async fn new(cfg: Arc<JetStreamCfg>) -> Result<Self, Box<dyn Error>> {
+        let client = async_nats::connect(cfg.nats_url.as_str()).await?;
+        let context = jetstream::new(client.clone());
+        let stream = context
+            .get_or_create_stream(jetstream::stream::Config {
+                name: cfg.stream.to_string(),
+                subjects: vec![events::ALL.to_string()],
+                max_age: Duration::from_secs(cfg.max_age_hours * 60 * 60),
+                ..Default::default()
+            })
+            .await?;
+
+        Ok(Self { cfg, client, context, stream })
+    }
+
+    pub async fn new_from_env() -> Result<Self, Box<dyn Error>> {
+        let cfg = Arc::new(JetStreamCfg::init_from_env()?);
+        Self::new(cfg).await
+    }
+
+    fn ping_connection(&self) -> Result<(), Box<dyn Error>> {
+        match self.client.connection_state() {
+            State::Connected => Ok(()),
+            state => Err(format!("nats connection is {}", state).into()),
+        }
+    }
+
+    async fn subscribe_durable(&self, subject: &str, group: &str) -> Result<MessageStream, Box<dyn Error>> {
+        let name = durable_name(group);
+        let config = pull::Config {
+            durable_name: Some(name.to_string()),
+            filter_subject: subject.to_string(),
+            ack_policy: AckPolicy::Explicit,
+            ack_wait: Duration::from_secs(self.cfg.ack_wait_secs),
+            max_deliver: self.cfg.max_deliver,
+            ..Default::default()
+        };
+        // an existing consumer keeps its config unless it is updated
+        let consumer: PullConsumer = match self.stream.consumer_info(name.as_str()).await {
+            Ok(info) if !is_outdated(&info.config, &config) => self.stream.get_consumer(name.as_str()).await.map_err(|e| e.to_string())?,
+            Ok(_) => {
+                println!("updating consumer config: {}", name);
+                self.stream.create_consumer(config).await?
+            }
+            Err(_) => self.stream.create_consumer(config).await?,
+        };
+        self.dead_letter_max_deliveries(name.as_str()).await?;
+
+        // one message at a time, so messages never wait for their ack deadline in a buffer
+        let messages = consumer.stream().max_messages_per_batch(1).messages().await?;
+
+        let cfg = self.cfg.clone();
+        let context = self.context.clone();
+        Ok(Box::pin(messages.filter_map(move |msg| {
+            future::ready(match msg {
+                Ok(msg) => {
+                    let subject = msg.subject.to_string();
+                    let payload = msg.payload.to_vec();
+                    let acker = JetStreamAcker::new(cfg.clone(), context.clone(), msg);
+                    Some(Message::with_acker(subject, payload, Arc::new(acker)))
+                }
+                Err(e) => {
+                    eprintln!("failed to receive message from jetstream: {}", e);
+                    None
+                }
+            })
+        })))
+    }
+
+    /// Messages which time out on their last delivery are never nacked, the server only announces
+    /// them with an advisory. Those are moved to the dead letters here.
+    async fn dead_letter_max_deliveries(&self, durable: &str) -> Result<(), Box<dyn Error>> {
+        let subject = format!("{}.{}.{}", MAX_DELIVERIES_ADVISORY, self.cfg.stream, durable);
+        let mut advisories = self.client.queue_subscribe(subject, events::DEAD_LETTER_GROUP.to_string()).await?;
+
+        let stream = self.stream.clone();
+        let context = self.context.clone();
+        tokio::spawn(async move {
+            while let Some(advisory) = advisories.next().await {
+                if let Err(e) = dead_letter_advisory(&stream, &context, advisory.payload).await.map_err(|e| e.to_string()) {
+                    eprintln!("failed to dead letter message after max deliveries: {}", e)
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventPublisher for JetStreamBus {
+    async fn publish_bytes(&self, subject: &str, payload: Vec<u8>) -> Result<(), Box<dyn Error>> {
+        // waits for the stream to have stored the event
+        let stored: Result<(), Box<dyn Error>> = async {
+            self.context.publish(subject.to_string(), payload.into()).await?.await?;
+            Ok(())
+        }.await;
+        if stored.is_err() {
+            darklight_metrics::PUBLISH_FAILURES.with_label_values(&[subject]).inc();
+        }
+
+        stored
+    }
+
+    async fn flush(&self) -> Result<(), Box<dyn Error>> {
+        self.client.flush().await?;
+
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<(), Box<dyn Error>> {
+        self.ping_connection()
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for JetStreamBus {
+    async fn subscribe(&self, subject: &str, group: Option<&str>) -> Result<MessageStream, Box<dyn Error>> {
+        if let Some(g) = group {
+            return self.subscribe_durable(subject, g).await;
+        }
+
+        let sub = self.client.subscribe(subject.to_string()).await?;
+        Ok(Box::pin(sub.map(|msg| Message::new(msg.subject.to_string(), msg.payload.to_vec()))))
+    }
+
+    async fn ping(&self) -> Result<(), Box<dyn Error>> {
+        self.ping_connection()
+    }
+}
+
+struct JetStreamAcker {
+    cfg: Arc<JetStreamCfg>,
+    context: jetstream::Context,
+    message: jetstream::Message,
+    keep_alive: CancellationToken,
+}
+
+impl JetStreamAcker {
+    /// Keeps telling the server the message is being worked on until it is settled, as
+    /// handlers like downloads run for much longer than the ack deadline.
+    fn new(cfg: Arc<JetStreamCfg>, context: jetstream::Context, message: jetstream::Message) -> Self {
+        let keep_alive = CancellationToken::new();
+        let interval = Duration::from_secs(cfg.ack_wait_secs.max(2) / 2);
+
+        let token = keep_alive.clone();
+        let msg = message.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = token.cancelled() => return,
+                    _ = tokio::time::sleep(interval) => {
+                        if let Err(e) = msg.ack_with(AckKind::Progress).await {
+                            eprintln!("failed to extend ack deadline: {}", e)
+                        }
+                    }
+                }
+            }
+        });
+
+        Self { cfg, context, message, keep_alive }
+    }
+
+    fn delivered(&self) -> i64 {
+        self.message.info().map(|info| info.delivered).unwrap_or(1)
+    }
+}
+
+impl Drop for JetStreamAcker {
+    fn drop(&mut self) {
+        self.keep_alive.cancel()
+    }
+}
+
+#[async_trait]
+impl Acknowledger for JetStreamAcker {
+    async fn ack(&self) -> Result<(), Box<dyn Error>> {
+        self.keep_alive.cancel();
+        self.message.ack().await.map_err(|e| e as Box<dyn Error>)?;
+
+        Ok(())
+    }
+
+    async fn nak(&self) -> Result<(), Box<dyn Error>> {
+        self.keep_alive.cancel();
+
+        if self.delivered() >= self.cfg.max_deliver {
+            return self.dead_letter("exceeded max deliveries").await;
+        }
+
+        let delay = Duration::from_secs(self.cfg.redelivery_delay_secs);
+        self.message.ack_with(AckKind::Nak(Some(delay))).await.map_err(|e| e as Box<dyn Error>)?;
+
+        Ok(())
+    }
+
+    async fn dead_letter(&self, reason: &str) -> Result<(), Box<dyn Error>> {
+        self.keep_alive.cancel();
+
+        let subject = self.message.subject.to_string();
+        publish_dead_letter(&self.context, subject.as_str(), self.message.payload.clone(), self.delivered(), reason).await?;
+
+        // stops redelivery, the message lives on below the dead letter subject
+        self.message.ack_with(AckKind::Term).await.map_err(|e| e as Box<dyn Error>)?;
+
+        Ok(())
+    }
+}
+
+// followed by the stream and the consumer
+const MAX_DELIVERIES_ADVISORY: &str = "$JS.EVENT.ADVISORY.CONSUMER.MAX_DELIVERIES";
+
+#[derive(Deserialize)]
+struct MaxDeliveriesAdvisory {
+    stream_seq: u64,
+    deliveries: i64,
+}
+
+async fn dead_letter_advisory(stream: &jetstream::stream::Stream, context: &jetstream::Context, advisory: Bytes) -> Result<(), Box<dyn Error>> {
+    let advisory: MaxDeliveriesAdvisory = serde_json::from_slice(&advisory)?;
+    let message = async_nats::Message::try_from(stream.get_raw_message(advisory.stream_seq).await.map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+
+    publish_dead_letter(context, message.subject.as_str(), message.payload, advisory.deliveries, "exceeded max deliveries without being settled").await
+}
+
+async fn publish_dead_letter(context: &jetstream::Context, subject: &str, payload: Bytes, delivered: i64, reason: &str) -> Result<(), Box<dyn Error>> {
+    let delivered = delivered.to_string();
+    let mut headers = HeaderMap::new();
+    headers.insert("Darklight-Original-Subject", subject);
+    headers.insert("Darklight-Dead-Letter-Reason", reason);
+    headers.insert("Darklight-Delivered", delivered.as_str());
+
+    context
+        .publish_with_headers(dead_letter_subject(subject), headers, payload)
+        .await?
+        .await?;
+    eprintln!("moved message on {} to dead letters: {}", subject, reason);
+
+    Ok(())
+}
+
+/// Whether the settings this process was configured with differ from the ones the consumer has.
+fn is_outdated(current: &consumer::Config, wanted: &pull::Config) -> bool {
+    current.ack_wait != wanted.ack_wait
+        || current.max_deliver != wanted.max_deliver
+        || current.filter_subject != wanted.filter_subject
+}
+
+fn durable_name(group: &str) -> String {
+    group.replace(|c: char| !c.is_ascii_alphanumeric() && c != '-' && c != '_', "-")
+}
+
+fn dead_letter_subject(subject: &str) -> String {
+    format!("{}.{}", events::DEAD_LETTER, subject)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_nats::jetstream::consumer::{self, pull};
+
+    use crate::events;
+    use crate::jetstream::{dead_letter_subject, durable_name, is_outdated};
+
+    #[test]
+    fn test_consumer_is_outdated_when_settings_change() {
+        let current = consumer::Config {
+            filter_subject: events::DOWNLOADS.into(),
+            ack_wait: Duration::from_secs(30),
+            max_deliver: 5,
+            ..Default::default()
+        };
+        let wanted = |max_deliver| pull::Config {
+            filter_subject: events::DOWNLOADS.into(),
+            ack_wait: Duration::from_secs(30),
+            max_deliver,
+            ..Default::default()
+        };
+
+        assert!(!is_outdated(&current, &wanted(5)));
+        assert!(is_outdated(&current, &wanted(10)));
+    }
+
+    #[test]
+    fn test_durable_name_is_valid_consumer_name() {
+        assert_eq!(durable_name(events::WORKER_GROUP), "darklight-worker");
+        assert_eq!(durable_name("a b.c>*"), "a-b-c--");
+    }
+
+    #[test]
+    fn test_dead_letter_subject() {
+        assert_eq!(dead_letter_subject(events::DOWNLOADS), "darklight.dead-letter.darklight.downloads");
+    }
+}
@@ -4,4 +4,18 @@ extern crate envconfig_derive;
 pub mod subscriber;
 pub mod publisher;
 pub mod models;
-pub mod events;
\ No newline at end of file
+pub mod envelope;
+pub mod events;
+pub mod event_bus;
+pub mod in_memory_bus;
+pub mod jetstream;
+mod ping;
+
+use crate::envconfig::Envconfig;
+
+#[derive(Envconfig)]
+pub struct EventBusCfg {
+    /// Either `nats`, `jetstream` or `memory`
+    #[envconfig(from = "EVENT_BUS", default = "nats")]
+    pub backend: String,
+}
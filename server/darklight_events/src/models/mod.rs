@@ -1,50 +1,139 @@
 use serde::{Deserialize, Serialize};
 
+use darklight_core::download::Download;
+use darklight_core::download_error::DownloadError;
+
+use crate::envelope::EventPayload;
+use crate::events;
+
 #[derive(Serialize, Deserialize)]
-pub struct DoneDownloading<'a> {
-    pub download_id: &'a str,
-    pub file_name: &'a str,
+pub struct DoneDownloading {
+    pub download_id: String,
+    pub file_name: String,
+    #[serde(default)]
+    pub file_size: Option<u64>,
+    #[serde(default)]
+    pub storage_key: Option<String>,
+    #[serde(default)]
+    pub checksum: Option<String>,
 }
 
-impl<'a> DoneDownloading<'a> {
-    pub fn new(download_id: &'a str, file_name: &'a str) -> Self {
+impl DoneDownloading {
+    pub fn new(download_id: &str, file_name: &str, file_size: u64, storage_key: &str, checksum: &str) -> Self {
         Self {
-            download_id,
-            file_name,
+            download_id: download_id.to_string(),
+            file_name: file_name.to_string(),
+            file_size: Some(file_size),
+            storage_key: Some(storage_key.to_string()),
+            checksum: Some(checksum.to_string()),
         }
     }
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct DownloadStatus<'a> {
-    pub download_id: &'a str,
+pub struct DownloadStatus {
+    pub download_id: String,
     pub percentage: u32,
 }
 
-impl<'a> DownloadStatus<'a> {
-    pub fn new(download_id: &'a str, percentage: u32) -> Self {
+impl DownloadStatus {
+    pub fn new(download_id: &str, percentage: u32) -> Self {
         Self {
-            download_id,
+            download_id: download_id.to_string(),
             percentage,
         }
     }
 }
 
-
 #[derive(Serialize, Deserialize)]
-pub struct DownloadFileNameAvailable<'a> {
-    pub download_id: &'a str,
+pub struct DownloadFileNameAvailable {
+    pub download_id: String,
     pub file_name: String,
 }
 
-impl<'a> DownloadFileNameAvailable<'a> {
-    pub fn new(download_id: &'a str, file_name: String) -> Self {
+impl DownloadFileNameAvailable {
+    pub fn new(download_id: &str, file_name: String) -> Self {
         Self {
-            download_id,
+            download_id: download_id.to_string(),
             file_name,
         }
     }
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct DownloadFailed {
+    pub download_id: String,
+    pub error: DownloadError,
+}
+
+impl DownloadFailed {
+    pub fn new(download_id: &str, error: DownloadError) -> Self {
+        Self {
+            download_id: download_id.to_string(),
+            error,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DownloadAttempt {
+    pub download_id: String,
+    pub attempt: u32,
+}
+
+impl DownloadAttempt {
+    pub fn new(download_id: &str, attempt: u32) -> Self {
+        Self {
+            download_id: download_id.to_string(),
+            attempt,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DownloadCancel {
+    pub download_id: String,
+}
+
+impl DownloadCancel {
+    pub fn new(download_id: &str) -> Self {
+        Self {
+            download_id: download_id.to_string(),
+        }
+    }
+}
+
+impl EventPayload for Download {
+    const SUBJECT: &'static str = events::DOWNLOADS;
+    const VERSION: u32 = 1;
+}
+
+impl EventPayload for DoneDownloading {
+    const SUBJECT: &'static str = events::DOWNLOAD_DONE;
+    const VERSION: u32 = 1;
+}
+
+impl EventPayload for DownloadStatus {
+    const SUBJECT: &'static str = events::DOWNLOAD_UPDATE;
+    const VERSION: u32 = 1;
+}
+
+impl EventPayload for DownloadFileNameAvailable {
+    const SUBJECT: &'static str = events::DOWNLOAD_FILE_NAME_AVAILABLE;
+    const VERSION: u32 = 1;
+}
 
+impl EventPayload for DownloadFailed {
+    const SUBJECT: &'static str = events::DOWNLOAD_FAILED;
+    const VERSION: u32 = 1;
+}
 
+impl EventPayload for DownloadAttempt {
+    const SUBJECT: &'static str = events::DOWNLOAD_ATTEMPT;
+    const VERSION: u32 = 1;
+}
+
+impl EventPayload for DownloadCancel {
+    const SUBJECT: &'static str = events::DOWNLOAD_CANCEL;
+    const VERSION: u32 = 1;
+}
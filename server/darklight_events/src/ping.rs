@@ -0,0 +1,27 @@
+use std::error::Error;
+use std::time::Duration;
+
+use futures::StreamExt;
+use ratsio::NatsClient;
+use uuid::Uuid;
+
+use crate::events;
+
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Sends a message to itself, which only arrives while the connection to the server is alive.
+pub(crate) async fn round_trip(conn: &NatsClient) -> Result<(), Box<dyn Error>> {
+    let subject = format!("{}.{}", events::PING, Uuid::new_v4());
+    let (sid, sub) = conn.subscribe(subject.to_string()).await?;
+    let mut sub = Box::pin(sub);
+
+    conn.publish(subject.as_str(), b"ping").await?;
+    let received = tokio::time::timeout(PING_TIMEOUT, sub.next()).await;
+    conn.un_subscribe(&sid).await?;
+
+    match received {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err("nats subscription closed".into()),
+        Err(_) => Err(format!("no reply from nats within {:?}", PING_TIMEOUT).into()),
+    }
+}
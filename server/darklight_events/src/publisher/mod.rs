@@ -1,10 +1,12 @@
 use std::error::Error;
 use std::sync::Arc;
 
+use async_trait::async_trait;
 use ratsio::NatsClient;
-use serde::Serialize;
 
 use crate::envconfig::Envconfig;
+use crate::event_bus::EventPublisher;
+use crate::ping;
 
 #[derive(Envconfig)]
 pub struct PublisherCfg {
@@ -27,14 +29,20 @@ impl Publisher {
         let publisher_cfg = Arc::new(PublisherCfg::init_from_env()?);
         Self::new(publisher_cfg).await
     }
+}
 
-    pub async fn publish<T>(&self, subject: &str, payload: T) -> Result<(), Box<dyn Error>>
-        where
-            T: Serialize {
-        let payload = serde_json::to_string(&payload)?;
-
-        self.conn.publish(subject, payload.as_bytes()).await?;
+#[async_trait]
+impl EventPublisher for Publisher {
+    async fn publish_bytes(&self, subject: &str, payload: Vec<u8>) -> Result<(), Box<dyn Error>> {
+        if let Err(e) = self.conn.publish(subject, payload.as_slice()).await {
+            darklight_metrics::PUBLISH_FAILURES.with_label_values(&[subject]).inc();
+            return Err(e.into());
+        }
 
         Ok(())
     }
+
+    async fn ping(&self) -> Result<(), Box<dyn Error>> {
+        ping::round_trip(&self.conn).await
+    }
 }
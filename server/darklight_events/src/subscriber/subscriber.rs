@@ -1,12 +1,13 @@
 use std::error::Error;
-use std::future::Future;
 use std::sync::Arc;
 
-use futures::{Stream, StreamExt};
-use ratsio::{NatsClient};
-use ratsio::ops::{Message};
+use async_trait::async_trait;
+use futures::StreamExt;
+use ratsio::NatsClient;
 
 use crate::envconfig::Envconfig;
+use crate::event_bus::{EventSubscriber, Message, MessageStream};
+use crate::ping;
 
 #[derive(Envconfig)]
 pub struct SubscriberCfg {
@@ -29,32 +30,23 @@ impl Subscriber {
         let subscriber_cfg = Arc::new(SubscriberCfg::init_from_env()?);
         Self::new(subscriber_cfg).await
     }
+}
 
-    pub async fn run<F, Fut>(&self, subject: &str, group: Option<&str>, handler: F) -> Result<(), Box<dyn Error>>
-        where
-            F: Fn(Message) -> Fut,
-            Fut: Future<Output=()>
-    {
-        if let Some(g) = group {
-            let (_, mut sub) = self.conn.subscribe_with_group(subject, g).await?;
+#[async_trait]
+impl EventSubscriber for Subscriber {
+    async fn subscribe(&self, subject: &str, group: Option<&str>) -> Result<MessageStream, Box<dyn Error>> {
+        let to_message = |msg: ratsio::ops::Message| Message::new(msg.subject, msg.payload);
 
-            while let Some(msg) = sub.next().await {
-                handler(msg).await
-            }
+        if let Some(g) = group {
+            let (_, sub) = self.conn.subscribe_with_group(subject.to_string(), g.to_string()).await?;
+            Ok(Box::pin(sub.map(to_message)))
         } else {
-            let (_, mut sub) = self.conn.subscribe(subject).await?;
-
-            while let Some(msg) = sub.next().await {
-                handler(msg).await
-            }
-        };
-
-
-        Ok(())
+            let (_, sub) = self.conn.subscribe(subject.to_string()).await?;
+            Ok(Box::pin(sub.map(to_message)))
+        }
     }
 
-    pub async fn get_stream(&self, subject: String) -> impl Stream<Item=Message> + Send + Sync {
-        let (_, sub) = self.conn.subscribe(subject).await.unwrap();
-        sub
+    async fn ping(&self) -> Result<(), Box<dyn Error>> {
+        ping::round_trip(&self.conn).await
     }
 }
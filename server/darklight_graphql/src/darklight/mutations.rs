@@ -1,5 +1,7 @@
 use crate::GraphQLDependencies;
-use async_graphql::{Context, Object, Result, SimpleObject, ID};
+use async_graphql::{Context, Enum, InputObject, Object, Result, SimpleObject, ID};
+use darklight_app::download_queue::QueuedDownload;
+use darklight_core::download_options::{AudioCodec, AudioExtraction, DownloadOptions, FormatPreset};
 
 
 pub struct MutationRoot;
@@ -7,6 +9,51 @@ pub struct MutationRoot;
 #[derive(SimpleObject)]
 struct RequestDownloadResp {
     id: ID,
+    is_batch: bool,
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+enum DownloadFormat {
+    BestVideo,
+    Max720p,
+    AudioOnly,
+}
+
+/// Exposes `AudioCodec` to the schema, under the name clients already use.
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(remote = "AudioCodec")]
+enum AudioFormat {
+    Mp3,
+    Opus,
+    M4a,
+    Flac,
+}
+
+#[derive(InputObject)]
+struct AudioExtractionInput {
+    format: AudioFormat,
+    bitrate: Option<u32>,
+}
+
+impl From<AudioExtractionInput> for AudioExtraction {
+    fn from(input: AudioExtractionInput) -> Self {
+        Self {
+            codec: input.format.into(),
+            bitrate: input.bitrate,
+        }
+    }
+}
+
+fn format_preset(format: Option<DownloadFormat>, format_id: Option<String>) -> FormatPreset {
+    if let Some(id) = format_id {
+        return FormatPreset::FormatId(id);
+    }
+
+    match format {
+        None | Some(DownloadFormat::BestVideo) => FormatPreset::BestVideo,
+        Some(DownloadFormat::Max720p) => FormatPreset::Max720p,
+        Some(DownloadFormat::AudioOnly) => FormatPreset::AudioOnly,
+    }
 }
 
 #[Object]
@@ -16,14 +63,65 @@ impl MutationRoot {
         ctx: &Context<'_>,
         link: String,
         requester_id: ID,
+        format: Option<DownloadFormat>,
+        format_id: Option<String>,
+        audio: Option<AudioExtractionInput>,
     ) -> Result<RequestDownloadResp> {
+        let options = DownloadOptions {
+            format: format_preset(format, format_id),
+            audio: audio.map(AudioExtraction::from),
+        };
+
+        match ctx
+            .data_unchecked::<GraphQLDependencies>()
+            .download_queue
+            .add(link.as_str(), requester_id.0, options)
+            .await
+        {
+            Ok(QueuedDownload::Download(id)) => Ok(RequestDownloadResp {
+                id: ID::from(id),
+                is_batch: false,
+            }),
+            Ok(QueuedDownload::Batch(id)) => Ok(RequestDownloadResp {
+                id: ID::from(id),
+                is_batch: true,
+            }),
+            Err(e) => Err(async_graphql::Error::new(e.to_string())),
+        }
+    }
+
+    async fn retry_download(&self, ctx: &Context<'_>, download_id: ID) -> Result<bool> {
+        match ctx
+            .data_unchecked::<GraphQLDependencies>()
+            .download_queue
+            .retry(download_id.as_str())
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => Err(async_graphql::Error::new(e.to_string())),
+        }
+    }
+
+    async fn pin_download(&self, ctx: &Context<'_>, download_id: ID, pinned: bool) -> Result<bool> {
+        match ctx
+            .data_unchecked::<GraphQLDependencies>()
+            .download_queue
+            .pin(download_id.as_str(), pinned)
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => Err(async_graphql::Error::new(e.to_string())),
+        }
+    }
+
+    async fn cancel_download(&self, ctx: &Context<'_>, download_id: ID) -> Result<bool> {
         match ctx
             .data_unchecked::<GraphQLDependencies>()
             .download_queue
-            .add(link.as_str(), requester_id.0)
+            .cancel(download_id.as_str())
             .await
         {
-            Ok(id) => Ok(RequestDownloadResp { id: ID::from(id) }),
+            Ok(_) => Ok(true),
             Err(e) => Err(async_graphql::Error::new(e.to_string())),
         }
     }
@@ -1,5 +1,4 @@
 use async_graphql::{Context, Object, Result, SimpleObject, ID};
-use std::error::Error;
 
 use crate::GraphQLDependencies;
 
@@ -10,6 +9,19 @@ pub struct Download {
     pub link: String,
     pub file: Option<String>,
     pub percentage: u32,
+    pub title: Option<String>,
+    pub duration: Option<u32>,
+    pub uploader: Option<String>,
+    pub thumbnail: Option<String>,
+    pub format: String,
+    pub audio_format: Option<String>,
+    pub batch_id: Option<ID>,
+    pub error_kind: Option<String>,
+    pub error: Option<String>,
+    pub attempts: u32,
+    pub file_size: Option<u64>,
+    pub pinned: bool,
+    pub checksum: Option<String>,
 }
 
 impl TryFrom<darklight_core::download::Download> for Download {
@@ -19,16 +31,110 @@ impl TryFrom<darklight_core::download::Download> for Download {
             return Err("id is missing from download".into());
         }
 
+        let metadata = d.metadata.unwrap_or_default();
+
         Ok(Self {
             id: ID::from(d.id.unwrap()),
             state: d.state.as_str().to_string(),
             link: d.link,
             file: d.file,
             percentage: d.percentage,
+            title: metadata.title,
+            duration: metadata.duration,
+            uploader: metadata.uploader,
+            thumbnail: metadata.thumbnail,
+            format: d.options.format.to_string(),
+            audio_format: d.options.audio.map(|a| a.codec.as_str().to_string()),
+            batch_id: d.batch_id.map(ID::from),
+            error_kind: d.error.as_ref().map(|e| e.kind.as_str().to_string()),
+            error: d.error.map(|e| e.message),
+            attempts: d.attempts,
+            file_size: d.file_size,
+            pinned: d.pinned,
+            checksum: d.checksum,
+        })
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct DownloadBatch {
+    pub id: ID,
+    pub link: String,
+    pub title: Option<String>,
+    pub total: u32,
+    pub done: u32,
+    pub failed: u32,
+    pub percentage: u32,
+    pub downloads: Vec<Download>,
+}
+
+impl TryFrom<darklight_core::download_batch::DownloadBatch> for DownloadBatch {
+    type Error = async_graphql::Error;
+    fn try_from(b: darklight_core::download_batch::DownloadBatch) -> std::result::Result<Self, Self::Error> {
+        if b.id.is_none() {
+            return Err("id is missing from batch".into());
+        }
+
+        let progress = b.progress();
+        Ok(Self {
+            id: ID::from(b.id.unwrap()),
+            link: b.link,
+            title: b.title,
+            total: progress.total,
+            done: progress.done,
+            failed: progress.failed,
+            percentage: progress.percentage,
+            downloads: b
+                .downloads
+                .into_iter()
+                .flat_map(|d| d.try_into())
+                .collect::<Vec<Download>>(),
         })
     }
 }
 
+#[derive(SimpleObject)]
+pub struct DownloadRun {
+    pub state: String,
+    pub file: Option<String>,
+    pub percentage: u32,
+    pub attempts: u32,
+    pub error_kind: Option<String>,
+    pub error: Option<String>,
+    pub insert_time: String,
+    pub archived_time: String,
+}
+
+impl From<darklight_core::download::DownloadRun> for DownloadRun {
+    fn from(r: darklight_core::download::DownloadRun) -> Self {
+        Self {
+            state: r.state.as_str().to_string(),
+            file: r.file,
+            percentage: r.percentage,
+            attempts: r.attempts,
+            error_kind: r.error.as_ref().map(|e| e.kind.as_str().to_string()),
+            error: r.error.map(|e| e.message),
+            insert_time: r.insert_time.to_rfc3339(),
+            archived_time: r.archived_time.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct ShareLink {
+    pub url: String,
+    pub expires_at: String,
+}
+
+impl From<darklight_core::share_link::ShareLink> for ShareLink {
+    fn from(l: darklight_core::share_link::ShareLink) -> Self {
+        Self {
+            url: l.url,
+            expires_at: l.expires_at.to_rfc3339(),
+        }
+    }
+}
+
 pub struct QueryRoot;
 
 #[Object]
@@ -53,6 +159,34 @@ impl QueryRoot {
         }
     }
 
+    async fn get_batch(&self, ctx: &Context<'_>, batch_id: ID) -> Result<Option<DownloadBatch>> {
+        match ctx
+            .data_unchecked::<GraphQLDependencies>()
+            .download_queue
+            .get_batch(batch_id.as_str())
+            .await
+        {
+            Ok(Some(b)) => match b.try_into() {
+                Ok(b) => Ok(Some(b)),
+                Err(e) => Err(e),
+            },
+            Ok(None) => Ok(None),
+            Err(e) => Err(async_graphql::Error::new(e.to_string())),
+        }
+    }
+
+    async fn share_link(&self, ctx: &Context<'_>, download_id: ID, ttl: Option<u32>) -> Result<ShareLink> {
+        match ctx
+            .data_unchecked::<GraphQLDependencies>()
+            .download_queue
+            .share_link(download_id.as_str(), ttl)
+            .await
+        {
+            Ok(link) => Ok(link.into()),
+            Err(e) => Err(async_graphql::Error::new(e.to_string())),
+        }
+    }
+
     async fn get_downloads(&self, ctx: &Context<'_>, requester_id: ID) -> Result<Vec<Download>> {
         match ctx
             .data_unchecked::<GraphQLDependencies>()
@@ -62,11 +196,21 @@ impl QueryRoot {
         {
             Ok(ds) => Ok(ds
                 .into_iter()
-                .map(|d| d.try_into())
-                .filter(|d| d.is_ok())
-                .map(|d| d.unwrap())
+                .flat_map(|d| d.try_into())
                 .collect::<Vec<Download>>()),
             Err(e) => Err(async_graphql::Error::new(e.to_string())),
         }
     }
+
+    async fn get_download_history(&self, ctx: &Context<'_>, download_id: ID) -> Result<Vec<DownloadRun>> {
+        match ctx
+            .data_unchecked::<GraphQLDependencies>()
+            .download_queue
+            .get_history(download_id.as_str())
+            .await
+        {
+            Ok(runs) => Ok(runs.into_iter().map(|r| r.into()).collect()),
+            Err(e) => Err(async_graphql::Error::new(e.to_string())),
+        }
+    }
 }
@@ -4,7 +4,6 @@ use async_graphql::{Context, Object, Result, Subscription, ID};
 
 use crate::darklight::queries;
 use crate::GraphQLDependencies;
-use darklight_events::events::DOWNLOAD_UPDATE;
 use darklight_events::models::DownloadStatus;
 
 pub struct SubscriptionRoot;
@@ -42,20 +41,18 @@ impl SubscriptionRoot {
         &self,
         ctx: &Context<'_>,
         download_id: ID,
-    ) -> impl Stream<Item = DownloadChanged> {
+    ) -> Result<impl Stream<Item = DownloadChanged>> {
         let stream = ctx
             .data_unchecked::<GraphQLDependencies>()
             .subscriber
-            .get_stream(DOWNLOAD_UPDATE.to_string())
-            .await;
+            .subscribe_event::<DownloadStatus>(None)
+            .await
+            .map_err(|e| async_graphql::Error::new(e.to_string()))?;
         let d_id = download_id.clone();
-        let next_stream = StreamExt::filter_map(stream, move |msg| {
+        let next_stream = StreamExt::filter_map(stream, move |event| {
             let d = d_id.clone();
             async move {
-                let payload = msg.payload.as_slice();
-                let download = serde_json::from_slice::<DownloadStatus>(payload).unwrap();
-
-                if download.download_id == d.clone().as_str() {
+                if event.payload.download_id == d.as_str() {
                     Some(DownloadChanged { id: d })
                 } else {
                     None
@@ -67,6 +64,6 @@ impl SubscriptionRoot {
             yield DownloadChanged { id: download_id.clone() }
         };
 
-        StreamExt::chain(initial_request, next_stream)
+        Ok(StreamExt::chain(initial_request, next_stream))
     }
 }
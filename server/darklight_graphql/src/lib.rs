@@ -4,15 +4,15 @@ use crate::darklight::{DarklightSchema, MutationRoot, QueryRoot, SubscriptionRoo
 use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
 use async_graphql::{Request, Response, Schema};
 use async_graphql_axum::GraphQLSubscription;
-use axum::headers::HeaderValue;
 use axum::http::Method;
 use axum::response::{Html, IntoResponse};
 use axum::routing::get;
 use axum::{http, Extension, Json, Router};
+use darklight_events::event_bus::EventSubscriber;
 use darklight_app::download_queue::DownloadQueue;
-use darklight_events::subscriber::subscriber::Subscriber;
 use darklight_persistence::repos::downloads::DownloadRepo;
 use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 use tower_http::cors::CorsLayer;
 
 async fn graphql_playground() -> impl IntoResponse {
@@ -26,14 +26,14 @@ async fn graphql_handler(schema: Extension<DarklightSchema>, req: Json<Request>)
 }
 
 pub struct GraphQLDependencies {
-    subscriber: Arc<Subscriber>,
+    subscriber: Arc<dyn EventSubscriber>,
     download_queue: Arc<DownloadQueue>,
     download_repo: Arc<DownloadRepo>,
 }
 
 impl GraphQLDependencies {
     pub fn new(
-        subscriber: Arc<Subscriber>,
+        subscriber: Arc<dyn EventSubscriber>,
         download_queue: Arc<DownloadQueue>,
         download_repo: Arc<DownloadRepo>,
     ) -> Self {
@@ -45,7 +45,8 @@ impl GraphQLDependencies {
     }
 }
 
-pub async fn run(deps: GraphQLDependencies) {
+/// Serves until `shutdown` is cancelled, letting requests in progress finish.
+pub async fn run(deps: GraphQLDependencies, shutdown: CancellationToken) {
     let schema = Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
         .data(deps)
         .finish();
@@ -68,6 +69,7 @@ pub async fn run(deps: GraphQLDependencies) {
 
     axum::Server::bind(&"0.0.0.0:8001".parse().unwrap())
         .serve(app.into_make_service())
+        .with_graceful_shutdown(async move { shutdown.cancelled().await })
         .await
         .unwrap()
 }
@@ -3,50 +3,39 @@ use std::{
     sync::Arc,
 };
 
+use tokio_util::sync::CancellationToken;
+
+use darklight_events::envelope::Event;
+use darklight_events::event_bus::EventSubscriber;
 use darklight_events::events;
 use darklight_events::models::DoneDownloading;
-use darklight_events::subscriber::subscriber::Subscriber;
 use darklight_persistence::repos::downloads::DownloadRepo;
 
-use crate::utility::parse_to_str;
-
 pub struct DoneDownloadingHandler {
-    subscriber: Arc<Subscriber>,
+    subscriber: Arc<dyn EventSubscriber>,
     download_repo: Arc<DownloadRepo>,
 }
 
 impl DoneDownloadingHandler {
-    pub fn new(subscriber: Arc<Subscriber>, download_repo: Arc<DownloadRepo>) -> Self {
+    pub fn new(subscriber: Arc<dyn EventSubscriber>, download_repo: Arc<DownloadRepo>) -> Self {
         Self { subscriber, download_repo }
     }
 
-    pub async fn run(self: Arc<Self>) {
-        if let Err(e) = self.subscriber.run(events::DOWNLOAD_DONE, Some(events::DONE_DOWNLOADING_GROUP), |msg| {
+    pub async fn run(self: Arc<Self>, stop: CancellationToken) {
+        if let Err(e) = self.subscriber.run_event_draining(Some(events::DONE_DOWNLOADING_GROUP), stop, |event: Event<DoneDownloading>| {
             let s = Arc::clone(&self);
-            async move {
-                if let Err(e) = s.run_done_downloading(&msg.payload).await {
-                    eprintln!("failed to run done downloading: {}", e)
-                }
-            }
+            async move { s.run_done_downloading(event.payload).await }
         }).await {
             eprintln!("{}", e)
         }
     }
 
-    async fn run_done_downloading(&self, payload: &Vec<u8>) -> Result<(), Box<dyn Error>> {
-        let download = parse_to_str(payload).and_then(serialize_download)?;
+    async fn run_done_downloading(&self, download: DoneDownloading) -> Result<(), Box<dyn Error>> {
         println!("Finished download: {}", download.download_id);
 
-        self.download_repo.finish_download(download.download_id, download.file_name).await?;
+        self.download_repo.finish_download(download.download_id.as_str(), download.file_name.as_str(), download.file_size, download.storage_key.as_deref(), download.checksum.as_deref()).await?;
         println!("Finished download, database updated: {}", download.download_id);
 
         Ok(())
     }
 }
-
-fn serialize_download(payload: &str) -> Result<DoneDownloading, Box<dyn Error>> {
-    match serde_json::from_str::<DoneDownloading>(payload) {
-        Ok(d) => Ok(d),
-        Err(e) => Err(e.into())
-    }
-}
@@ -0,0 +1,41 @@
+use std::{
+    error::Error,
+    sync::Arc,
+};
+
+use tokio_util::sync::CancellationToken;
+
+use darklight_events::envelope::Event;
+use darklight_events::event_bus::EventSubscriber;
+use darklight_events::events;
+use darklight_events::models::DownloadAttempt;
+use darklight_persistence::repos::downloads::DownloadRepo;
+
+pub struct DownloadAttemptHandler {
+    subscriber: Arc<dyn EventSubscriber>,
+    download_repo: Arc<DownloadRepo>,
+}
+
+impl DownloadAttemptHandler {
+    pub fn new(subscriber: Arc<dyn EventSubscriber>, download_repo: Arc<DownloadRepo>) -> Self {
+        Self { subscriber, download_repo }
+    }
+
+    pub async fn run(self: Arc<Self>, stop: CancellationToken) {
+        if let Err(e) = self.subscriber.run_event_draining(Some(events::DOWNLOAD_ATTEMPT_GROUP), stop, |event: Event<DownloadAttempt>| {
+            let s = Arc::clone(&self);
+            async move { s.run_download_attempt(event.payload).await }
+        }).await {
+            eprintln!("{}", e)
+        }
+    }
+
+    async fn run_download_attempt(&self, download: DownloadAttempt) -> Result<(), Box<dyn Error>> {
+        println!("Download attempt: {}, {}", download.download_id, download.attempt);
+
+        self.download_repo.update_attempts(download.download_id.as_str(), download.attempt).await?;
+        println!("Download attempt, database updated: {}", download.download_id);
+
+        Ok(())
+    }
+}
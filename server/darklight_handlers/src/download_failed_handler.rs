@@ -0,0 +1,41 @@
+use std::{
+    error::Error,
+    sync::Arc,
+};
+
+use tokio_util::sync::CancellationToken;
+
+use darklight_events::envelope::Event;
+use darklight_events::event_bus::EventSubscriber;
+use darklight_events::events;
+use darklight_events::models::DownloadFailed;
+use darklight_persistence::repos::downloads::DownloadRepo;
+
+pub struct DownloadFailedHandler {
+    subscriber: Arc<dyn EventSubscriber>,
+    download_repo: Arc<DownloadRepo>,
+}
+
+impl DownloadFailedHandler {
+    pub fn new(subscriber: Arc<dyn EventSubscriber>, download_repo: Arc<DownloadRepo>) -> Self {
+        Self { subscriber, download_repo }
+    }
+
+    pub async fn run(self: Arc<Self>, stop: CancellationToken) {
+        if let Err(e) = self.subscriber.run_event_draining(Some(events::DOWNLOAD_FAILED_GROUP), stop, |event: Event<DownloadFailed>| {
+            let s = Arc::clone(&self);
+            async move { s.run_download_failed(event.payload).await }
+        }).await {
+            eprintln!("{}", e)
+        }
+    }
+
+    async fn run_download_failed(&self, download: DownloadFailed) -> Result<(), Box<dyn Error>> {
+        println!("Failed download: {}, {}", download.download_id, download.error);
+
+        self.download_repo.fail_download(download.download_id.as_str(), &download.error).await?;
+        println!("Failed download, database updated: {}", download.download_id);
+
+        Ok(())
+    }
+}
@@ -1,73 +1,427 @@
-use std::error::Error;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use futures::future;
+use tokio_util::sync::CancellationToken;
 
 use darklight_app::file_downloader::FileDownloader;
+use darklight_app::worker_pool::WorkerPool;
 use darklight_core::download::Download;
+use darklight_core::download_error::{DownloadError, DownloadErrorKind};
+use darklight_events::envelope::Event;
+use darklight_events::event_bus::{EventPublisher, EventSubscriber};
 use darklight_events::events;
-use darklight_events::models::DoneDownloading;
-use darklight_events::publisher::Publisher;
-use darklight_events::subscriber::subscriber::Subscriber;
-use darklight_storage::storage_uploader::FileUploader;
+use darklight_events::models::{DoneDownloading, DownloadAttempt, DownloadCancel, DownloadFailed};
+use darklight_persistence::repos::downloads::DownloadRepo;
+use darklight_storage::checksum::{content_key, sha256_file};
+use darklight_storage::storage_backend::StorageBackend;
 
-use crate::utility::parse_to_str;
+use crate::recovery_policy::RecoveryPolicy;
+use crate::retry_policy::RetryPolicy;
+use crate::shutdown_policy::ShutdownPolicy;
 
 pub struct DownloadWorker {
-    subscriber: Arc<Subscriber>,
-    publisher: Arc<Publisher>,
+    subscriber: Arc<dyn EventSubscriber>,
+    publisher: Arc<dyn EventPublisher>,
     file_downloader: Arc<FileDownloader>,
-    file_uploader: Arc<FileUploader>,
+    storage: Arc<dyn StorageBackend>,
+    download_repo: Arc<DownloadRepo>,
+    retry_policy: Arc<RetryPolicy>,
+    recovery_policy: Arc<RecoveryPolicy>,
+    worker_pool: Arc<WorkerPool>,
+    shutdown_policy: Arc<ShutdownPolicy>,
+    // cancelled once shutdown starts, downloads which haven't started yet are requeued
+    stopping: CancellationToken,
+    // cancelled once the drain timeout is reached, running downloads are requeued
+    interrupt: CancellationToken,
+    in_flight: Mutex<HashMap<String, CancellationToken>>,
 }
 
 impl DownloadWorker {
-    pub fn new(subscriber: Arc<Subscriber>, publisher: Arc<Publisher>, file_downloader: Arc<FileDownloader>, file_uploader: Arc<FileUploader>) -> Self {
-        Self { subscriber, publisher, file_downloader, file_uploader }
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(subscriber: Arc<dyn EventSubscriber>, publisher: Arc<dyn EventPublisher>, file_downloader: Arc<FileDownloader>, storage: Arc<dyn StorageBackend>, download_repo: Arc<DownloadRepo>, retry_policy: Arc<RetryPolicy>, recovery_policy: Arc<RecoveryPolicy>, worker_pool: Arc<WorkerPool>, shutdown_policy: Arc<ShutdownPolicy>) -> Self {
+        Self {
+            subscriber,
+            publisher,
+            file_downloader,
+            storage,
+            download_repo,
+            retry_policy,
+            recovery_policy,
+            worker_pool,
+            shutdown_policy,
+            stopping: CancellationToken::new(),
+            interrupt: CancellationToken::new(),
+            in_flight: Mutex::new(HashMap::new()),
+        }
     }
 
-    pub async fn run(self: Arc<Self>) {
-        if let Err(e) = self.subscriber.run(events::DOWNLOADS, Some(events::WORKER_GROUP), |msg| {
+    /// Runs until `shutdown` is cancelled and the downloads still running have been drained.
+    pub async fn run(self: Arc<Self>, shutdown: CancellationToken) {
+        let drained = CancellationToken::new();
+
+        let _ = tokio::join!(
+            async {
+                Arc::clone(&self).run_downloads(shutdown.clone()).await;
+                drained.cancel();
+            },
+            // downloads being drained can still be cancelled
+            Arc::clone(&self).run_cancellations(drained.clone()),
+            Arc::clone(&self).interrupt_after_drain_timeout(shutdown.clone(), drained.clone()),
+        );
+    }
+
+    async fn interrupt_after_drain_timeout(self: Arc<Self>, shutdown: CancellationToken, drained: CancellationToken) {
+        tokio::select! {
+            _ = shutdown.cancelled() => {}
+            _ = drained.cancelled() => return,
+        }
+        self.stopping.cancel();
+        println!("draining {} running downloads", self.in_flight.lock().unwrap().len());
+
+        tokio::select! {
+            _ = tokio::time::sleep(self.shutdown_policy.drain_timeout) => {
+                println!("drain timeout reached, requeueing running downloads");
+                self.interrupt.cancel();
+            }
+            _ = drained.cancelled() => {}
+        }
+    }
+
+    async fn run_cancellations(self: Arc<Self>, stop: CancellationToken) {
+        // every worker has to see the cancellation, so no queue group. Downloads which haven't
+        // started yet are skipped once their state is read, so only running ones are tracked here
+        if let Err(e) = self.subscriber.run_event(None, stop, |event: Event<DownloadCancel>| {
             let s = Arc::clone(&self);
             async move {
-                match parse_to_str(&msg.payload).and_then(serialize_download) {
-                    Ok(payload) => {
-                        match s.file_downloader.download(&payload).await {
-                            Ok(file_name) => {
-                                match s.file_downloader.get_file(payload.id.as_ref().unwrap()).await {
-                                    Ok(content) => match s.file_uploader.upload(file_name.clone(), &content).await {
-                                        Ok(_) => {
-                                            if let Err(e) = s.publisher.publish(events::DOWNLOAD_DONE, DoneDownloading::new(payload.id.as_ref().unwrap().as_str(), file_name.as_str())).await {
-                                                eprintln!("failed to publish event: {}", e)
-                                            }
-                                            println!("succeeded in uploading file")
-                                        }
-                                        Err(e) => {
-                                            eprintln!("{}", e);
-                                        }
-                                    },
-                                    Err(e) => {
-                                        eprintln!("{}", e);
-                                    }
-                                }
-                            }
-
-                            Err(e) => {
-                                eprintln!("{}", e)
-                            }
+                s.cancel(event.payload.download_id.as_str());
+                Ok(())
+            }
+        }).await {
+            eprintln!("{}", e)
+        }
+    }
+
+    fn cancel(&self, download_id: &str) {
+        if let Some(token) = self.in_flight.lock().unwrap().get(download_id) {
+            println!("cancelling download: {}", download_id);
+            token.cancel()
+        }
+    }
+
+    async fn run_downloads(self: Arc<Self>, shutdown: CancellationToken) {
+        let workers = (0..self.worker_pool.workers()).map(|_| Arc::clone(&self).run_download_worker(shutdown.clone()));
+
+        future::join_all(workers).await;
+    }
+
+    async fn run_download_worker(self: Arc<Self>, shutdown: CancellationToken) {
+        let max_in_flight = self.worker_pool.max_in_flight();
+
+        if let Err(e) = self.subscriber.run_event_concurrently(Some(events::WORKER_GROUP), max_in_flight, shutdown, |event: Event<Download>| {
+            let s = Arc::clone(&self);
+            async move {
+                let _message = s.worker_pool.hold_message();
+                // failures are published as events, the message itself was handled
+                s.download_with_retries(&event).await;
+                Ok(())
+            }
+        }).await {
+            eprintln!("{}", e)
+        }
+    }
+
+    async fn download_with_retries(&self, event: &Event<Download>) {
+        let download = &event.payload;
+        let download_id = download.id.as_ref().unwrap().as_str();
+        let cancellation = self.interrupt.child_token();
+        // registered before the state is read, so a cancellation arriving in between isn't missed
+        self.in_flight.lock().unwrap().insert(download_id.to_string(), cancellation.clone());
+
+        if self.is_settled(download_id).await {
+            println!("skipping settled download: {}", download_id);
+            self.in_flight.lock().unwrap().remove(download_id);
+            return;
+        }
+
+        let started = Instant::now();
+        let outcome = tokio::select! {
+            outcome = self.download_with_cancellation(download, event.correlation_id.as_str(), cancellation) => outcome,
+            never = self.renew_lease(download_id) => match never {},
+        };
+        darklight_metrics::DOWNLOADS.with_label_values(&[outcome]).inc();
+        darklight_metrics::DOWNLOAD_DURATION.with_label_values(&[outcome]).observe(started.elapsed().as_secs_f64());
+
+        self.in_flight.lock().unwrap().remove(download_id);
+    }
+
+    async fn download_with_cancellation(&self, download: &Download, correlation_id: &str, cancellation: CancellationToken) -> &'static str {
+        let download_id = download.id.as_ref().unwrap().as_str();
+        let mut attempt = download.attempts;
+
+        loop {
+            attempt += 1;
+            if let Err(e) = self.publisher.publish_event(&Event::correlated(correlation_id, DownloadAttempt::new(download_id, attempt))).await {
+                eprintln!("failed to publish event: {}", e)
+            }
+
+            match self.download_and_upload(download, correlation_id, cancellation.clone()).await {
+                Ok(uploaded) => {
+                    let done = DoneDownloading::new(download_id, uploaded.file_name.as_str(), uploaded.file_size, uploaded.storage_key.as_str(), uploaded.checksum.as_str());
+                    if let Err(e) = self.publisher.publish_event(&Event::correlated(correlation_id, done)).await {
+                        eprintln!("failed to publish event: {}", e)
+                    }
+                    darklight_metrics::DOWNLOAD_BYTES.observe(uploaded.file_size as f64);
+                    println!("succeeded in uploading file");
+                    return "done";
+                }
+                Err(e) if self.is_interrupted(&e) => {
+                    self.requeue(download, correlation_id).await;
+                    return "requeued";
+                }
+                Err(e) if e.kind.is_retryable() && self.retry_policy.should_retry(attempt) => {
+                    let backoff = self.retry_policy.backoff(attempt);
+                    eprintln!("attempt {} failed for {}, retrying in {:?}: {}", attempt, download_id, backoff, e);
+                    // a partial file left by the failed attempt would be picked up as the download
+                    self.clean_up(download_id).await;
+                    tokio::select! {
+                        _ = tokio::time::sleep(backoff) => {}
+                        // another worker can retry it, instead of holding up the shutdown
+                        _ = self.stopping.cancelled() => {
+                            self.requeue(download, correlation_id).await;
+                            return "requeued";
+                        }
+                        _ = cancellation.cancelled() => {
+                            self.clean_up(download_id).await;
+                            return "cancelled";
                         }
                     }
-                    Err(e) => {
-                        eprint!("{}", e);
+                }
+                Err(e) if e.kind == DownloadErrorKind::Cancelled => {
+                    println!("cancelled download: {}", download_id);
+                    self.clean_up(download_id).await;
+                    return "cancelled";
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    if let Err(e) = self.publisher.publish_event(&Event::correlated(correlation_id, DownloadFailed::new(download_id, e))).await {
+                        eprintln!("failed to publish event: {}", e)
                     }
+                    return "failed";
                 }
             }
-        }).await {
-            eprintln!("{}", e)
         }
     }
+
+    /// Whether the download was cancelled or already finished before this worker got to it. A
+    /// retried download is reset before it is requested again, so it still runs.
+    async fn is_settled(&self, download_id: &str) -> bool {
+        match self.download_repo.get_by_download_id(download_id).await {
+            Ok(Some(download)) => download.state.is_terminal(),
+            Ok(None) => true,
+            Err(e) => {
+                // better to download twice than to lose the download
+                eprintln!("failed to look up download {}: {}", download_id, e);
+                false
+            }
+        }
+    }
+
+    /// References the stored object from the download and returns whether it is already stored.
+    /// Both happen under the object lock, so the retention job can't delete the object in between.
+    async fn reference_object(&self, download_id: &str, storage_key: &str, checksum: &str) -> Result<bool, String> {
+        let mut lock = self.download_repo.lock_object(storage_key).await.map_err(|e| e.to_string())?;
+        let stored = self.storage.exists(storage_key).await.map_err(|e| e.to_string())?;
+        lock.reference(download_id, checksum).await.map_err(|e| e.to_string())?;
+        lock.commit().await.map_err(|e| e.to_string())?;
+
+        Ok(stored)
+    }
+
+    /// Keeps the download from looking stalled while it waits for a slot or uploads, which
+    /// publish no progress.
+    async fn renew_lease(&self, download_id: &str) -> Infallible {
+        let mut interval = tokio::time::interval(self.recovery_policy.lease_renewal_interval());
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.download_repo.touch_download(download_id).await.map_err(|e| e.to_string()) {
+                eprintln!("failed to renew lease of {}: {}", download_id, e)
+            }
+        }
+    }
+
+    fn is_interrupted(&self, e: &DownloadError) -> bool {
+        match e.kind {
+            DownloadErrorKind::Interrupted => true,
+            DownloadErrorKind::Cancelled => self.interrupt.is_cancelled(),
+            _ => false,
+        }
+    }
+
+    /// Hands a download this worker won't finish back to the queue, for another worker to start over.
+    async fn requeue(&self, download: &Download, correlation_id: &str) {
+        let download_id = download.id.as_ref().unwrap().as_str();
+        println!("requeueing interrupted download: {}", download_id);
+
+        if let Err(e) = self.publisher.publish_event(&Event::correlated(correlation_id, download.clone())).await {
+            eprintln!("failed to publish event: {}", e)
+        }
+        self.clean_up(download_id).await;
+    }
+
+    async fn clean_up(&self, download_id: &str) {
+        if let Err(e) = self.file_downloader.clean_up(download_id).await {
+            eprintln!("failed to clean up {}: {}", download_id, e)
+        }
+    }
+
+    async fn download_and_upload(&self, download: &Download, correlation_id: &str, cancellation: CancellationToken) -> Result<UploadedFile, DownloadError> {
+        let slot = tokio::select! {
+            slot = self.worker_pool.acquire(download.link.as_str()) => slot,
+            _ = cancellation.cancelled() => return Err(DownloadError::new(DownloadErrorKind::Cancelled, "cancelled while waiting for a free worker")),
+            _ = self.stopping.cancelled() => return Err(DownloadError::new(DownloadErrorKind::Interrupted, "shutting down before the download started")),
+        };
+        let downloaded = self.file_downloader.download(download, correlation_id, cancellation).await;
+        // uploading doesn't run yt-dlp, so the next download can already start
+        drop(slot);
+        let file_name = downloaded?;
+
+        let file_path = match self.file_downloader.get_file_path(download.id.as_ref().unwrap()).await {
+            Ok(file_path) => file_path,
+            Err(e) => return Err(DownloadError::new(DownloadErrorKind::Unknown, e.to_string())),
+        };
+
+        let file_size = match tokio::fs::metadata(&file_path).await {
+            Ok(metadata) => metadata.len(),
+            Err(e) => return Err(DownloadError::new(DownloadErrorKind::Unknown, e.to_string())),
+        };
+
+        let checksum = match sha256_file(&file_path).await {
+            Ok(checksum) => checksum,
+            Err(e) => return Err(DownloadError::new(DownloadErrorKind::Unknown, e.to_string())),
+        };
+        let storage_key = content_key(checksum.as_str());
+
+        // identical content is already stored, whatever it was called
+        match self.reference_object(download.id.as_ref().unwrap(), storage_key.as_str(), checksum.as_str()).await {
+            Ok(true) => {
+                println!("file already stored: {}", storage_key);
+            }
+            Ok(false) => {
+                let timer = darklight_metrics::UPLOAD_DURATION.start_timer();
+                let uploaded = self.storage.put_file(storage_key.as_str(), &file_path).await.map_err(|e| e.to_string());
+                timer.observe_duration();
+                if let Err(e) = uploaded {
+                    return Err(DownloadError::new(DownloadErrorKind::UploadFailed, e));
+                }
+            }
+            Err(e) => return Err(DownloadError::new(DownloadErrorKind::UploadFailed, e)),
+        }
+
+        Ok(UploadedFile {
+            file_name,
+            file_size,
+            storage_key,
+            checksum,
+        })
+    }
+}
+
+struct UploadedFile {
+    file_name: String,
+    file_size: u64,
+    storage_key: String,
+    checksum: String,
 }
 
-fn serialize_download(payload: &str) -> Result<Download, Box<dyn Error>> {
-    match serde_json::from_str::<Download>(payload) {
-        Ok(d) => Ok(d),
-        Err(e) => Err(e.into())
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use futures::StreamExt;
+    use sqlx::postgres::PgPoolOptions;
+    use tokio_util::sync::CancellationToken;
+
+    use darklight_app::file_downloader::{FileDownloader, FileDownloaderCfg};
+    use darklight_app::worker_pool::{WorkerPool, WorkerPoolCfg};
+    use darklight_core::download_error::DownloadErrorKind;
+    use darklight_core::test_util::DownloadBuilder;
+    use darklight_events::envelope::{Event, EventPayload};
+    use darklight_events::event_bus::{EventPublisher, EventSubscriber, MessageStream};
+    use darklight_events::events;
+    use darklight_events::in_memory_bus::{InMemoryBus, InMemoryBusCfg};
+    use darklight_events::models::{DownloadAttempt, DownloadFailed};
+    use darklight_persistence::postgres::PostgresDb;
+    use darklight_persistence::repos::downloads::DownloadRepo;
+    use darklight_storage::local_storage::{LocalStorage, LocalStorageCfg};
+
+    use crate::download_worker::DownloadWorker;
+    use crate::recovery_policy::{RecoveryPolicy, RecoveryPolicyCfg};
+    use crate::retry_policy::{RetryPolicy, RetryPolicyCfg};
+    use crate::shutdown_policy::{ShutdownPolicy, ShutdownPolicyCfg};
+
+    async fn worker(bus: Arc<InMemoryBus>, path: &std::path::Path) -> Arc<DownloadWorker> {
+        // nothing listens there, so the worker has to get by without its database
+        let pool = PgPoolOptions::new()
+            .connect_timeout(Duration::from_millis(100))
+            .connect_lazy("postgres://darklight@127.0.0.1:1/darklight")
+            .unwrap();
+        let file_downloader = FileDownloader::new(Arc::new(FileDownloaderCfg {
+            storage_path: path.join("output").to_string_lossy().to_string(),
+        }), bus.clone());
+        let storage = LocalStorage::new(LocalStorageCfg {
+            path: path.join("storage").to_string_lossy().to_string(),
+        }).await.unwrap();
+
+        Arc::new(DownloadWorker::new(
+            bus.clone(),
+            bus,
+            Arc::new(file_downloader),
+            Arc::new(storage),
+            Arc::new(DownloadRepo::new(Arc::new(PostgresDb { pool }))),
+            Arc::new(RetryPolicy::new(&RetryPolicyCfg { max_attempts: 3, initial_backoff_ms: 10, max_backoff_ms: 10 })),
+            Arc::new(RecoveryPolicy::new(&RecoveryPolicyCfg { lease_timeout_secs: 600, action: "requeue".into(), start_delay_secs: 0 }, 3).unwrap()),
+            Arc::new(WorkerPool::new(WorkerPoolCfg { workers: 1, max_in_flight: 1, max_downloads: 1, max_downloads_per_domain: 1 })),
+            Arc::new(ShutdownPolicy::new(&ShutdownPolicyCfg { drain_timeout_secs: 1 })),
+        ))
+    }
+
+    async fn next_event<T: EventPayload>(stream: &mut MessageStream) -> T {
+        let msg = tokio::time::timeout(Duration::from_secs(10), stream.next()).await.unwrap().unwrap();
+        Event::<T>::decode(&msg.payload).unwrap().payload
+    }
+
+    #[tokio::test]
+    async fn test_publishes_attempt_and_failure_of_failed_download() {
+        let path = std::env::temp_dir().join(format!("darklight-worker-{}", std::process::id()));
+        let bus = Arc::new(InMemoryBus::new(Arc::new(InMemoryBusCfg { capacity: 16 })));
+        let mut attempts = bus.subscribe(events::DOWNLOAD_ATTEMPT, None).await.unwrap();
+        let mut failures = bus.subscribe(events::DOWNLOAD_FAILED, None).await.unwrap();
+
+        let shutdown = CancellationToken::new();
+        let running = tokio::spawn(worker(bus.clone(), &path).await.run(shutdown.clone()));
+        // gives the worker time to subscribe
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let download = DownloadBuilder::new().id("download").link("not-a-link").build();
+        let publisher: Arc<dyn EventPublisher> = bus.clone();
+        publisher.publish_event(&Event::new(download)).await.unwrap();
+
+        let attempt: DownloadAttempt = next_event(&mut attempts).await;
+        assert_eq!(attempt.download_id, "download");
+        assert_eq!(attempt.attempt, 1);
+
+        // fails whether yt-dlp is missing or refuses the link, neither is retried
+        let failed: DownloadFailed = next_event(&mut failures).await;
+        assert_eq!(failed.download_id, "download");
+        assert!(!failed.error.kind.is_retryable());
+        assert_ne!(failed.error.kind, DownloadErrorKind::Cancelled);
+
+        shutdown.cancel();
+        running.await.unwrap();
+        let _ = tokio::fs::remove_dir_all(&path).await;
     }
 }
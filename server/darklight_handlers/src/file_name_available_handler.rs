@@ -3,50 +3,39 @@ use std::{
     sync::Arc,
 };
 
+use tokio_util::sync::CancellationToken;
+
+use darklight_events::envelope::Event;
+use darklight_events::event_bus::EventSubscriber;
 use darklight_events::events;
 use darklight_events::models::DownloadFileNameAvailable;
-use darklight_events::subscriber::subscriber::Subscriber;
 use darklight_persistence::repos::downloads::DownloadRepo;
 
-use crate::utility::parse_to_str;
-
 pub struct FileNameAvailableHandler {
-    subscriber: Arc<Subscriber>,
+    subscriber: Arc<dyn EventSubscriber>,
     download_repo: Arc<DownloadRepo>,
 }
 
 impl FileNameAvailableHandler {
-    pub fn new(subscriber: Arc<Subscriber>, download_repo: Arc<DownloadRepo>) -> Self {
+    pub fn new(subscriber: Arc<dyn EventSubscriber>, download_repo: Arc<DownloadRepo>) -> Self {
         Self { subscriber, download_repo }
     }
 
-    pub async fn run(self: Arc<Self>) {
-        if let Err(e) = self.subscriber.run(events::DOWNLOAD_FILE_NAME_AVAILABLE, Some(events::DOWNLOAD_FILE_NAME_AVAILABLE_GROUP), |msg| {
+    pub async fn run(self: Arc<Self>, stop: CancellationToken) {
+        if let Err(e) = self.subscriber.run_event_draining(Some(events::DOWNLOAD_FILE_NAME_AVAILABLE_GROUP), stop, |event: Event<DownloadFileNameAvailable>| {
             let s = Arc::clone(&self);
-            async move {
-                if let Err(e) = s.update_file_name(&msg.payload).await {
-                    eprintln!("failed to update with file name: {}", e)
-                }
-            }
+            async move { s.update_file_name(event.payload).await }
         }).await {
             eprintln!("{}", e)
         }
     }
 
-    async fn update_file_name(&self, payload: &Vec<u8>) -> Result<(), Box<dyn Error>> {
-        let download = parse_to_str(payload).and_then(serialize_download)?;
+    async fn update_file_name(&self, download: DownloadFileNameAvailable) -> Result<(), Box<dyn Error>> {
         println!("Update file name: {}", download.download_id);
 
-        self.download_repo.update_file_name(download.download_id, download.file_name).await?;
+        self.download_repo.update_file_name(download.download_id.as_str(), download.file_name).await?;
         println!("Updated file name, database updated: {}", download.download_id);
 
         Ok(())
     }
 }
-
-fn serialize_download(payload: &str) -> Result<DownloadFileNameAvailable, Box<dyn Error>> {
-    match serde_json::from_str::<DownloadFileNameAvailable>(payload) {
-        Ok(d) => Ok(d),
-        Err(e) => Err(e.into())
-    }
-}
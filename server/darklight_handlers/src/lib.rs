@@ -1,73 +1,126 @@
+extern crate envconfig;
+extern crate envconfig_derive;
+
 use std::sync::Arc;
 
+use tokio_util::sync::CancellationToken;
+
+use darklight_events::event_bus::{EventPublisher, EventSubscriber};
 use darklight_app::file_downloader::FileDownloader;
-use darklight_events::publisher::Publisher;
-use darklight_events::subscriber::subscriber::Subscriber;
+use darklight_app::worker_pool::WorkerPool;
 use darklight_persistence::repos::downloads::DownloadRepo;
-use darklight_storage::storage_uploader::FileUploader;
+use darklight_storage::storage_backend::StorageBackend;
 
 use crate::done_downloading_handler::DoneDownloadingHandler;
+use crate::download_attempt_handler::DownloadAttemptHandler;
+use crate::download_failed_handler::DownloadFailedHandler;
 use crate::download_worker::DownloadWorker;
 use crate::file_name_available_handler::FileNameAvailableHandler;
+use crate::outbox_relay::OutboxRelay;
+use crate::recovery_job::RecoveryJob;
+use crate::recovery_policy::RecoveryPolicy;
+use crate::retention_job::RetentionJob;
+use crate::retention_policy::RetentionPolicy;
+use crate::retry_policy::RetryPolicy;
+use crate::shutdown_policy::ShutdownPolicy;
+use crate::state_gauges_job::StateGaugesJob;
 use crate::status_update_handler::StatusUpdateHandler;
 
 pub mod download_worker;
 pub mod done_downloading_handler;
 pub mod status_update_handler;
 pub mod file_name_available_handler;
-mod utility;
-
-//let external_queue = download_queue.clone();
-//let sched = JobScheduler::new().unwrap();
-//sched
-//.add(
-//Job::new_async("1/30 * * * * *", move |_uuid, _l| {
-//let external_queue = external_queue.clone();
-//Box::pin(async move {
-//external_queue.remove_old().await.unwrap();
-//})
-//}).unwrap(),
-//).unwrap();
-
-
-//sched.start().unwrap();
+pub mod download_failed_handler;
+pub mod download_attempt_handler;
+pub mod outbox_relay;
+pub mod recovery_job;
+pub mod recovery_policy;
+pub mod retry_policy;
+pub mod shutdown_policy;
+pub mod retention_job;
+pub mod retention_policy;
+pub mod state_gauges_job;
 
 pub struct HandlerDependencies {
-    subscriber: Arc<Subscriber>,
-    publisher: Arc<Publisher>,
+    subscriber: Arc<dyn EventSubscriber>,
+    publisher: Arc<dyn EventPublisher>,
     file_downloader: Arc<FileDownloader>,
-    file_uploader: Arc<FileUploader>,
+    storage: Arc<dyn StorageBackend>,
     download_repo: Arc<DownloadRepo>,
+    retry_policy: Arc<RetryPolicy>,
+    retention_policy: Arc<RetentionPolicy>,
+    recovery_policy: Arc<RecoveryPolicy>,
+    worker_pool: Arc<WorkerPool>,
+    shutdown_policy: Arc<ShutdownPolicy>,
+    outbox_relay: Arc<OutboxRelay>,
+    state_gauges_job: Arc<StateGaugesJob>,
 }
 
 impl HandlerDependencies {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        subscriber: Arc<Subscriber>,
-        publisher: Arc<Publisher>,
+        subscriber: Arc<dyn EventSubscriber>,
+        publisher: Arc<dyn EventPublisher>,
         file_downloader: Arc<FileDownloader>,
-        file_uploader: Arc<FileUploader>,
+        storage: Arc<dyn StorageBackend>,
         download_repo: Arc<DownloadRepo>,
+        retry_policy: Arc<RetryPolicy>,
+        retention_policy: Arc<RetentionPolicy>,
+        recovery_policy: Arc<RecoveryPolicy>,
+        worker_pool: Arc<WorkerPool>,
+        shutdown_policy: Arc<ShutdownPolicy>,
+        outbox_relay: Arc<OutboxRelay>,
+        state_gauges_job: Arc<StateGaugesJob>,
     ) -> Self {
         Self {
             subscriber,
             publisher,
             file_downloader,
-            file_uploader,
+            storage,
             download_repo,
+            retry_policy,
+            retention_policy,
+            recovery_policy,
+            worker_pool,
+            shutdown_policy,
+            outbox_relay,
+            state_gauges_job,
         }
     }
 }
 
-pub async fn run_handlers(deps: HandlerDependencies) {
-    let download_worker = Arc::new(DownloadWorker::new(deps.subscriber.clone(), deps.publisher.clone(), deps.file_downloader.clone(), deps.file_uploader.clone()));
+/// Runs until `shutdown` is cancelled. Downloads are drained first, then everything else stops once
+/// the events the downloads published are handled.
+pub async fn run_handlers(deps: HandlerDependencies, shutdown: CancellationToken) {
+    let download_worker = Arc::new(DownloadWorker::new(deps.subscriber.clone(), deps.publisher.clone(), deps.file_downloader.clone(), deps.storage.clone(), deps.download_repo.clone(), deps.retry_policy.clone(), deps.recovery_policy.clone(), deps.worker_pool.clone(), deps.shutdown_policy.clone()));
     let done_downloading_handler = Arc::new(DoneDownloadingHandler::new(deps.subscriber.clone(), deps.download_repo.clone()));
     let status_update_handler = Arc::new(StatusUpdateHandler::new(deps.subscriber.clone(), deps.download_repo.clone()));
     let file_name_available_handler = Arc::new(FileNameAvailableHandler::new(deps.subscriber.clone(), deps.download_repo.clone()));
+    let download_failed_handler = Arc::new(DownloadFailedHandler::new(deps.subscriber.clone(), deps.download_repo.clone()));
+    let download_attempt_handler = Arc::new(DownloadAttemptHandler::new(deps.subscriber.clone(), deps.download_repo.clone()));
+    let retention_job = Arc::new(RetentionJob::new(deps.retention_policy.clone(), deps.download_repo.clone(), deps.storage.clone(), deps.file_downloader.clone()));
+    let recovery_job = Arc::new(RecoveryJob::new(deps.recovery_policy.clone(), deps.download_repo.clone(), deps.publisher.clone(), deps.file_downloader.clone()));
+
+    let stop_handlers = CancellationToken::new();
 
     let _ = tokio::join!(
-        download_worker.run(),
-        done_downloading_handler.run(),
-        status_update_handler.run(),
-        file_name_available_handler.run(),
+        async {
+            download_worker.run(shutdown.clone()).await;
+            // the handlers drain what was delivered to them, which includes everything flushed
+            if let Err(e) = deps.publisher.flush().await {
+                eprintln!("failed to flush events: {}", e)
+            }
+            stop_handlers.cancel();
+        },
+        done_downloading_handler.run(stop_handlers.clone()),
+        status_update_handler.run(stop_handlers.clone()),
+        file_name_available_handler.run(stop_handlers.clone()),
+        download_failed_handler.run(stop_handlers.clone()),
+        download_attempt_handler.run(stop_handlers.clone()),
+        retention_job.run(shutdown.clone()),
+        recovery_job.run(shutdown.clone()),
+        deps.outbox_relay.clone().run(stop_handlers.clone()),
+        deps.state_gauges_job.clone().run(shutdown.clone()),
     );
+    println!("handlers stopped");
 }
@@ -0,0 +1,107 @@
+use std::{
+    error::Error,
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::Utc;
+use tokio_util::sync::CancellationToken;
+
+use darklight_events::event_bus::EventPublisher;
+use darklight_persistence::repos::outbox::{OutboxClaim, OutboxMessage, OutboxRepo};
+
+use crate::envconfig::Envconfig;
+
+#[derive(Envconfig)]
+pub struct OutboxRelayCfg {
+    #[envconfig(from = "OUTBOX_POLL_INTERVAL_MS", default = "500")]
+    pub poll_interval_ms: u64,
+
+    #[envconfig(from = "OUTBOX_BATCH_SIZE", default = "100")]
+    pub batch_size: u32,
+
+    /// How long sent messages are kept around before they are deleted
+    #[envconfig(from = "OUTBOX_SENT_RETENTION_HOURS", default = "24")]
+    pub sent_retention_hours: i64,
+}
+
+/// Publishes the messages written to the outbox, at least once. Each relay publishes the messages it
+/// claimed in the order they were written, relays in other processes claim the ones after them.
+pub struct OutboxRelay {
+    cfg: Arc<OutboxRelayCfg>,
+    outbox_repo: Arc<OutboxRepo>,
+    publisher: Arc<dyn EventPublisher>,
+}
+
+impl OutboxRelay {
+    pub fn new(cfg: Arc<OutboxRelayCfg>, outbox_repo: Arc<OutboxRepo>, publisher: Arc<dyn EventPublisher>) -> Self {
+        Self { cfg, outbox_repo, publisher }
+    }
+
+    pub fn new_from_env(outbox_repo: Arc<OutboxRepo>, publisher: Arc<dyn EventPublisher>) -> Result<Self, Box<dyn Error>> {
+        let cfg = Arc::new(OutboxRelayCfg::init_from_env()?);
+        Ok(Self::new(cfg, outbox_repo, publisher))
+    }
+
+    /// Relays until `stop` is cancelled, then relays what is left one last time.
+    pub async fn run(self: Arc<Self>, stop: CancellationToken) {
+        let mut interval = tokio::time::interval(Duration::from_millis(self.cfg.poll_interval_ms));
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = stop.cancelled() => break,
+            }
+
+            if let Err(e) = self.relay_pending().await {
+                eprintln!("failed to relay outbox messages: {}", e)
+            }
+        }
+
+        if let Err(e) = self.relay_pending().await {
+            eprintln!("failed to relay outbox messages: {}", e)
+        }
+    }
+
+    /// Publishes pending messages until the outbox is drained, returning how many were sent.
+    pub async fn relay_pending(&self) -> Result<usize, Box<dyn Error>> {
+        let mut sent = 0;
+
+        loop {
+            let mut claim = self.outbox_repo.claim_pending(self.cfg.batch_size).await?;
+            let messages = std::mem::take(&mut claim.messages);
+            let batch_len = messages.len();
+
+            let published = self.publish_claimed(&mut claim, messages).await.map_err(|e| e.to_string());
+            // the messages published before a failure stay marked as sent
+            claim.commit().await?;
+            sent += published?;
+
+            if batch_len < self.cfg.batch_size as usize {
+                break;
+            }
+        }
+
+        if sent > 0 {
+            println!("relayed {} outbox messages", sent);
+            let cutoff = Utc::now() - chrono::Duration::hours(self.cfg.sent_retention_hours);
+            self.outbox_repo.delete_sent_before(cutoff).await?;
+        }
+
+        Ok(sent)
+    }
+
+    async fn publish_claimed(&self, claim: &mut OutboxClaim, messages: Vec<OutboxMessage>) -> Result<usize, Box<dyn Error>> {
+        let mut sent = 0;
+
+        for message in messages {
+            let id = message.id.ok_or("outbox message without id")?;
+            // stops at the first failure, so later messages never overtake earlier ones
+            self.publisher.publish_bytes(message.subject.as_str(), message.payload).await?;
+            claim.mark_sent(id).await?;
+            sent += 1;
+        }
+
+        Ok(sent)
+    }
+}
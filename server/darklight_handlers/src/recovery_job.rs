@@ -0,0 +1,104 @@
+use std::{
+    error::Error,
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+use tokio_util::sync::CancellationToken;
+
+use darklight_app::file_downloader::FileDownloader;
+use darklight_core::download::Download;
+use darklight_core::download_error::{DownloadError, DownloadErrorKind};
+use darklight_events::envelope::Event;
+use darklight_events::event_bus::EventPublisher;
+use darklight_persistence::repos::downloads::DownloadRepo;
+
+use crate::recovery_policy::{RecoveryAction, RecoveryPolicy};
+
+/// Picks up the downloads a crashed process left behind, once on startup.
+pub struct RecoveryJob {
+    policy: Arc<RecoveryPolicy>,
+    download_repo: Arc<DownloadRepo>,
+    publisher: Arc<dyn EventPublisher>,
+    file_downloader: Arc<FileDownloader>,
+}
+
+impl RecoveryJob {
+    pub fn new(policy: Arc<RecoveryPolicy>, download_repo: Arc<DownloadRepo>, publisher: Arc<dyn EventPublisher>, file_downloader: Arc<FileDownloader>) -> Self {
+        Self { policy, download_repo, publisher, file_downloader }
+    }
+
+    pub async fn run(self: Arc<Self>, stop: CancellationToken) {
+        tokio::select! {
+            _ = tokio::time::sleep(self.policy.start_delay) => {}
+            // requeueing makes no sense when no worker of this process picks the downloads up
+            _ = stop.cancelled() => return,
+        }
+
+        if let Err(e) = self.run_once().await {
+            eprintln!("failed to run recovery job: {}", e)
+        }
+    }
+
+    pub async fn run_once(&self) -> Result<(), Box<dyn Error>> {
+        let cutoff = self.policy.stalled_before(Utc::now());
+        let stalled = self.download_repo.get_stalled_downloads(cutoff).await?;
+        println!("recovering {} stalled downloads", stalled.len());
+
+        for download in &stalled {
+            if let Err(e) = self.recover(download, cutoff).await {
+                eprintln!("failed to recover {}: {}", download.id.as_deref().unwrap_or_default(), e)
+            }
+        }
+
+        self.clean_up_orphaned_dirs().await
+    }
+
+    async fn recover(&self, download: &Download, cutoff: DateTime<Utc>) -> Result<(), Box<dyn Error>> {
+        let download_id = download.id.as_deref().ok_or("download without id")?;
+
+        // restarts the lease, so the download isn't recovered twice while it waits for a worker. Only
+        // one process wins it, and a worker which reported on it since keeps it.
+        if !self.download_repo.claim_stalled_download(download_id, cutoff).await? {
+            println!("stalled download was picked up elsewhere: {}", download_id);
+            return Ok(());
+        }
+
+        match self.policy.action_for(download) {
+            RecoveryAction::Requeue => {
+                self.publisher.publish_event(&Event::new(download.clone())).await?;
+                println!("requeued stalled download: {}", download_id);
+            }
+            RecoveryAction::Fail => {
+                let error = DownloadError::new(DownloadErrorKind::Interrupted, "the download stalled and was given up on");
+                self.download_repo.fail_download(download_id, &error).await?;
+                self.file_downloader.clean_up(download_id).await?;
+                println!("failed stalled download: {}", download_id);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the working directories of downloads which are no longer running. Directories of
+    /// requeued downloads are kept, so partial files can be resumed.
+    async fn clean_up_orphaned_dirs(&self) -> Result<(), Box<dyn Error>> {
+        for download_id in self.file_downloader.list_download_dirs().await? {
+            let orphaned = match self.download_repo.get_by_download_id(download_id.as_str()).await {
+                Ok(Some(download)) => download.state.is_terminal(),
+                Ok(None) => true,
+                Err(e) => {
+                    eprintln!("failed to look up download of directory {}: {}", download_id, e);
+                    continue;
+                }
+            };
+
+            if orphaned {
+                println!("removing orphaned directory: {}", download_id);
+                self.file_downloader.clean_up(download_id.as_str()).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+use std::error::Error;
+
+use chrono::{DateTime, Duration, Utc};
+
+use darklight_core::download::Download;
+
+use crate::envconfig::Envconfig;
+
+#[derive(Envconfig)]
+pub struct RecoveryPolicyCfg {
+    /// How long a running download may go without updates before it counts as stalled
+    #[envconfig(from = "RECOVERY_LEASE_TIMEOUT_SECS", default = "900")]
+    pub lease_timeout_secs: u32,
+
+    /// Either `requeue` or `fail`
+    #[envconfig(from = "RECOVERY_ACTION", default = "requeue")]
+    pub action: String,
+
+    /// Gives the worker of this process time to subscribe before stalled downloads are requeued
+    #[envconfig(from = "RECOVERY_START_DELAY_SECS", default = "5")]
+    pub start_delay_secs: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecoveryAction {
+    Requeue,
+    Fail,
+}
+
+impl RecoveryAction {
+    pub fn from_string(s: &str) -> Option<Self> {
+        match s {
+            "requeue" => Some(RecoveryAction::Requeue),
+            "fail" => Some(RecoveryAction::Fail),
+            _ => None,
+        }
+    }
+}
+
+pub struct RecoveryPolicy {
+    pub start_delay: std::time::Duration,
+    lease_timeout: Duration,
+    action: RecoveryAction,
+    max_attempts: u32,
+}
+
+impl RecoveryPolicy {
+    /// Downloads which already used up `max_attempts` are failed instead of requeued.
+    pub fn new(cfg: &RecoveryPolicyCfg, max_attempts: u32) -> Result<Self, Box<dyn Error>> {
+        let action = RecoveryAction::from_string(cfg.action.as_str())
+            .ok_or_else(|| format!("unsupported recovery action: {}", cfg.action))?;
+
+        Ok(Self {
+            start_delay: std::time::Duration::from_secs(cfg.start_delay_secs),
+            lease_timeout: Duration::seconds(cfg.lease_timeout_secs as i64),
+            action,
+            max_attempts,
+        })
+    }
+
+    pub fn new_from_env(max_attempts: u32) -> Result<Self, Box<dyn Error>> {
+        let recovery_policy_cfg = RecoveryPolicyCfg::init_from_env()?;
+        Self::new(&recovery_policy_cfg, max_attempts)
+    }
+
+    /// Running downloads without updates since the returned time are stalled.
+    pub fn stalled_before(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now - self.lease_timeout
+    }
+
+    /// How often workers renew the lease of the downloads they hold, well within the timeout.
+    pub fn lease_renewal_interval(&self) -> std::time::Duration {
+        (self.lease_timeout / 3).to_std().unwrap_or_default().max(std::time::Duration::from_secs(1))
+    }
+
+    pub fn action_for(&self, download: &Download) -> RecoveryAction {
+        match self.action {
+            RecoveryAction::Requeue if download.attempts < self.max_attempts => RecoveryAction::Requeue,
+            _ => RecoveryAction::Fail,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use darklight_core::test_util::DownloadBuilder;
+
+    use crate::recovery_policy::{RecoveryAction, RecoveryPolicy, RecoveryPolicyCfg};
+
+    fn policy(action: &str) -> RecoveryPolicy {
+        RecoveryPolicy::new(&RecoveryPolicyCfg {
+            lease_timeout_secs: 600,
+            action: action.into(),
+            start_delay_secs: 0,
+        }, 3)
+        .unwrap()
+    }
+
+    #[test]
+    fn test_requeues_until_attempts_are_used_up() {
+        let policy = policy("requeue");
+
+        assert_eq!(policy.action_for(&DownloadBuilder::new().attempts(2).build()), RecoveryAction::Requeue);
+        assert_eq!(policy.action_for(&DownloadBuilder::new().attempts(3).build()), RecoveryAction::Fail);
+    }
+
+    #[test]
+    fn test_fail_policy_never_requeues() {
+        assert_eq!(policy("fail").action_for(&DownloadBuilder::new().attempts(0).build()), RecoveryAction::Fail);
+    }
+
+    #[test]
+    fn test_stalled_before_lease_timeout() {
+        let now = Utc::now();
+
+        assert_eq!(policy("requeue").stalled_before(now), now - Duration::minutes(10));
+    }
+
+    #[test]
+    fn test_renews_leases_well_within_timeout() {
+        assert_eq!(policy("requeue").lease_renewal_interval(), std::time::Duration::from_secs(200));
+    }
+
+    #[test]
+    fn test_rejects_unknown_action() {
+        assert!(RecoveryPolicy::new(&RecoveryPolicyCfg {
+            lease_timeout_secs: 600,
+            action: "ignore".into(),
+            start_delay_secs: 0,
+        }, 3)
+        .is_err());
+    }
+}
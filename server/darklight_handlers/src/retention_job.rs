@@ -0,0 +1,142 @@
+use std::{
+    error::Error,
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+use tokio_cron_scheduler::{Job, JobScheduler};
+use tokio_util::sync::CancellationToken;
+
+use darklight_app::file_downloader::FileDownloader;
+use darklight_core::download::Download;
+use darklight_persistence::repos::downloads::DownloadRepo;
+use darklight_storage::storage_backend::StorageBackend;
+
+use crate::retention_policy::RetentionPolicy;
+
+const RETENTION_JOB: &str = "retention";
+/// Candidates loaded at once
+const CANDIDATE_PAGE_SIZE: i64 = 500;
+
+pub struct RetentionJob {
+    policy: Arc<RetentionPolicy>,
+    download_repo: Arc<DownloadRepo>,
+    storage: Arc<dyn StorageBackend>,
+    file_downloader: Arc<FileDownloader>,
+}
+
+impl RetentionJob {
+    pub fn new(policy: Arc<RetentionPolicy>, download_repo: Arc<DownloadRepo>, storage: Arc<dyn StorageBackend>, file_downloader: Arc<FileDownloader>) -> Self {
+        Self { policy, download_repo, storage, file_downloader }
+    }
+
+    pub async fn run(self: Arc<Self>, stop: CancellationToken) {
+        match self.schedule() {
+            // the scheduler runs in the background until it is shut down
+            Ok(mut sched) => {
+                stop.cancelled().await;
+                if let Err(e) = sched.shutdown() {
+                    eprintln!("failed to shut down retention job: {:?}", e)
+                }
+            }
+            Err(e) => eprintln!("failed to schedule retention job: {}", e),
+        }
+    }
+
+    fn schedule(self: Arc<Self>) -> Result<JobScheduler, Box<dyn Error>> {
+        // JobSchedulerError only implements Debug in a usable way
+        let sched = JobScheduler::new().map_err(|e| format!("{:?}", e))?;
+        let schedule = self.policy.schedule.clone();
+        let job = Job::new_async(schedule.as_str(), move |_uuid, _l| {
+            let s = Arc::clone(&self);
+            Box::pin(async move {
+                if let Err(e) = s.run_once().await {
+                    eprintln!("failed to run retention job: {}", e)
+                }
+            })
+        })
+        .map_err(|e| format!("{:?}", e))?;
+
+        sched.add(job).map_err(|e| format!("{:?}", e))?;
+        sched.start().map_err(|e| format!("{:?}", e))?;
+
+        Ok(sched)
+    }
+
+    pub async fn run_once(&self) -> Result<(), Box<dyn Error>> {
+        // every replica schedules the job, only one of them runs a pass at a time
+        let lock = match self.download_repo.try_lock_job(RETENTION_JOB).await? {
+            Some(lock) => lock,
+            None => {
+                println!("retention job is already running elsewhere");
+                return Ok(());
+            }
+        };
+        println!("retention job triggered");
+
+        let now = Utc::now();
+        let mut after: Option<(DateTime<Utc>, String)> = None;
+        loop {
+            let downloads = self
+                .download_repo
+                .get_retention_candidates(after.as_ref().map(|(time, id)| (*time, id.as_str())), CANDIDATE_PAGE_SIZE)
+                .await?;
+            let last = match downloads.last() {
+                Some(last) => last,
+                None => break,
+            };
+            let stored_bytes = self.download_repo.get_stored_bytes().await?;
+
+            for download in self.policy.select_expired(&downloads, stored_bytes, now) {
+                if let Err(e) = self.expire(download).await {
+                    eprintln!("failed to expire {}: {}", download.id.as_ref().unwrap(), e)
+                }
+            }
+
+            after = Some((
+                last.update_time.or(last.insert_time).ok_or("download has no insert time")?,
+                last.id.clone().ok_or("download has no id")?,
+            ));
+        }
+
+        lock.release().await
+    }
+
+    async fn expire(&self, download: &Download) -> Result<(), Box<dyn Error>> {
+        let download_id = download.id.as_ref().unwrap();
+
+        // expire first, so the file is no longer offered for reuse while it is being deleted
+        self.download_repo.expire_download(download_id).await?;
+        self.file_downloader.clean_up(download_id).await?;
+
+        // earlier runs of a retried download keep their files until the download expires
+        let history = self.download_repo.get_download_history(download_id).await?;
+        let mut object_keys: Vec<&str> = download.object_key().into_iter().collect();
+        for object_key in history.iter().filter_map(|run| run.object_key()) {
+            if !object_keys.contains(&object_key) {
+                object_keys.push(object_key);
+            }
+        }
+
+        for object_key in object_keys {
+            self.release(download_id, object_key).await?;
+        }
+
+        println!("expired download: {}", download_id);
+        Ok(())
+    }
+
+    async fn release(&self, download_id: &str, object_key: &str) -> Result<(), Box<dyn Error>> {
+        // the file is shared with other downloads of the same media. The lock keeps workers and
+        // reuse from referencing it again while it is deleted.
+        let mut lock = self.download_repo.lock_object(object_key).await?;
+        if lock.count_references(download_id).await? == 0 {
+            self.storage.delete(object_key).await?;
+            lock.commit_deleted().await?;
+        } else {
+            lock.commit().await?;
+        }
+
+        Ok(())
+    }
+}
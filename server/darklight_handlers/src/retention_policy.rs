@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use chrono::{DateTime, Duration, Utc};
+
+use darklight_core::download::Download;
+use darklight_core::download_state::DownloadState;
+
+use crate::envconfig::Envconfig;
+
+#[derive(Envconfig)]
+pub struct RetentionPolicyCfg {
+    /// 0 keeps downloads regardless of age
+    #[envconfig(from = "RETENTION_MAX_AGE_HOURS", default = "168")]
+    pub max_age_hours: u32,
+
+    /// 0 keeps downloads regardless of their total size
+    #[envconfig(from = "RETENTION_MAX_TOTAL_BYTES", default = "0")]
+    pub max_total_bytes: u64,
+
+    #[envconfig(from = "RETENTION_KEEP_PINNED", default = "true")]
+    pub keep_pinned: bool,
+
+    /// Cron expression including seconds, e.g. every 15 minutes
+    #[envconfig(from = "RETENTION_SCHEDULE", default = "0 */15 * * * *")]
+    pub schedule: String,
+}
+
+pub struct RetentionPolicy {
+    pub schedule: String,
+    max_age: Option<Duration>,
+    max_total_bytes: Option<u64>,
+    keep_pinned: bool,
+}
+
+impl RetentionPolicy {
+    pub fn new(cfg: &RetentionPolicyCfg) -> Self {
+        Self {
+            schedule: cfg.schedule.clone(),
+            max_age: match cfg.max_age_hours {
+                0 => None,
+                hours => Some(Duration::hours(hours as i64)),
+            },
+            max_total_bytes: match cfg.max_total_bytes {
+                0 => None,
+                bytes => Some(bytes),
+            },
+            keep_pinned: cfg.keep_pinned,
+        }
+    }
+
+    pub fn new_from_env() -> Result<Self, Box<dyn Error>> {
+        let retention_policy_cfg = RetentionPolicyCfg::init_from_env()?;
+        Ok(Self::new(&retention_policy_cfg))
+    }
+
+    /// Picks the downloads to expire out of a page of `downloads`: everything settled longer ago
+    /// than the max age, and then the oldest remaining downloads until the `stored_bytes` of all
+    /// finished downloads are within the limit. Running downloads are never picked.
+    pub fn select_expired<'a>(&self, downloads: &'a [Download], stored_bytes: u64, now: DateTime<Utc>) -> Vec<&'a Download> {
+        let settled: Vec<&Download> = downloads
+            .iter()
+            .filter(|d| d.state.is_terminal() && !matches!(d.state, DownloadState::Expired))
+            .collect();
+
+        // downloads of the same media share a file, which is only freed with the last of them. Files
+        // shared with downloads outside of the page count as freed, the next page starts over from
+        // the bytes actually stored.
+        let mut references: HashMap<&str, u32> = HashMap::new();
+        for object_key in settled.iter().filter_map(|d| d.object_key()) {
+            *references.entry(object_key).or_insert(0) += 1;
+        }
+        let mut stored_bytes = stored_bytes;
+
+        let mut expired = Vec::new();
+        let mut kept = Vec::new();
+        for download in settled {
+            let too_old = match (self.max_age, settled_time(download)) {
+                (Some(max_age), Some(settled_time)) => settled_time + max_age < now,
+                _ => false,
+            };
+
+            if too_old && !self.is_kept(download) {
+                release(&mut references, &mut stored_bytes, download);
+                expired.push(download);
+            } else {
+                kept.push(download);
+            }
+        }
+
+        if let Some(max_total_bytes) = self.max_total_bytes {
+            kept.sort_by_key(|d| settled_time(d));
+
+            for download in kept {
+                if stored_bytes <= max_total_bytes {
+                    break;
+                }
+                if self.is_kept(download) {
+                    continue;
+                }
+
+                release(&mut references, &mut stored_bytes, download);
+                expired.push(download);
+            }
+        }
+
+        expired
+    }
+
+    fn is_kept(&self, download: &Download) -> bool {
+        self.keep_pinned && download.pinned
+    }
+}
+
+fn release<'a>(references: &mut HashMap<&'a str, u32>, stored_bytes: &mut u64, download: &'a Download) {
+    if let Some(count) = download.object_key().and_then(|k| references.get_mut(k)) {
+        *count -= 1;
+        if *count == 0 {
+            *stored_bytes = stored_bytes.saturating_sub(download.file_size.unwrap_or(0));
+        }
+    }
+}
+
+/// When the download reached its final state, downloads settled before this was recorded fall
+/// back to when they were requested.
+fn settled_time(download: &Download) -> Option<DateTime<Utc>> {
+    download.update_time.or(download.insert_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use darklight_core::download::Download;
+    use darklight_core::download_state::DownloadState;
+    use darklight_core::test_util::DownloadBuilder;
+
+    use crate::retention_policy::{RetentionPolicy, RetentionPolicyCfg};
+
+    fn policy(max_age_hours: u32, max_total_bytes: u64) -> RetentionPolicy {
+        RetentionPolicy::new(&RetentionPolicyCfg {
+            max_age_hours,
+            max_total_bytes,
+            keep_pinned: true,
+            schedule: "0 */15 * * * *".into(),
+        })
+    }
+
+    fn ids(downloads: Vec<&Download>) -> Vec<&str> {
+        downloads.iter().map(|d| d.id.as_deref().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_expires_old_downloads() {
+        let now = Utc::now();
+        let downloads = vec![
+            DownloadBuilder::new().id("old").state(DownloadState::Done).stored("old.mp4", 10).settled_at(now - Duration::hours(25)).build(),
+            DownloadBuilder::new().id("old-pinned").state(DownloadState::Done).stored("old-pinned.mp4", 10).settled_at(now - Duration::hours(25)).pinned().build(),
+            DownloadBuilder::new().id("old-running").state(DownloadState::Downloading).stored("old-running.mp4", 10).settled_at(now - Duration::hours(25)).build(),
+            DownloadBuilder::new().id("new").state(DownloadState::Error).stored("new.mp4", 10).settled_at(now - Duration::hours(1)).build(),
+        ];
+
+        assert_eq!(ids(policy(24, 0).select_expired(&downloads, 40, now)), vec!["old"]);
+    }
+
+    #[test]
+    fn test_age_counts_from_last_update() {
+        let now = Utc::now();
+        let finished_late = DownloadBuilder::new().id("finished-late").state(DownloadState::Done).stored("finished-late.mp4", 10)
+            .requested_at(now - Duration::hours(48)).settled_at(now - Duration::hours(1)).build();
+        let legacy = DownloadBuilder::new().id("legacy").state(DownloadState::Done).stored("legacy.mp4", 10)
+            .requested_at(now - Duration::hours(48)).build();
+
+        assert_eq!(ids(policy(24, 0).select_expired(&[finished_late, legacy], 20, now)), vec!["legacy"]);
+    }
+
+    #[test]
+    fn test_expires_oldest_downloads_over_size_limit() {
+        let now = Utc::now();
+        let downloads = vec![
+            DownloadBuilder::new().id("newest").state(DownloadState::Done).stored("newest.mp4", 40).settled_at(now - Duration::hours(1)).build(),
+            DownloadBuilder::new().id("oldest").state(DownloadState::Done).stored("oldest.mp4", 40).settled_at(now - Duration::hours(3)).build(),
+            DownloadBuilder::new().id("pinned").state(DownloadState::Done).stored("pinned.mp4", 40).settled_at(now - Duration::hours(4)).pinned().build(),
+            DownloadBuilder::new().id("middle").state(DownloadState::Done).stored("middle.mp4", 40).settled_at(now - Duration::hours(2)).build(),
+        ];
+
+        assert_eq!(ids(policy(0, 100).select_expired(&downloads, 160, now)), vec!["oldest", "middle"]);
+    }
+
+    #[test]
+    fn test_shared_files_count_once_towards_size_limit() {
+        let now = Utc::now();
+        let downloads = vec![
+            DownloadBuilder::new().id("original").state(DownloadState::Done).stored("original.mp4", 60).settled_at(now - Duration::hours(3)).build(),
+            DownloadBuilder::new().id("shared").state(DownloadState::Done).stored("original.mp4", 60).settled_at(now - Duration::hours(2)).build(),
+            DownloadBuilder::new().id("other").state(DownloadState::Done).stored("other.mp4", 30).settled_at(now - Duration::hours(1)).build(),
+        ];
+
+        assert!(policy(0, 100).select_expired(&downloads, 90, now).is_empty());
+        assert_eq!(ids(policy(0, 50).select_expired(&downloads, 90, now)), vec!["original", "shared"]);
+    }
+
+    #[test]
+    fn test_size_limit_counts_files_outside_the_page() {
+        let now = Utc::now();
+        let downloads = vec![
+            DownloadBuilder::new().id("oldest").state(DownloadState::Done).stored("oldest.mp4", 40).settled_at(now - Duration::hours(3)).build(),
+            DownloadBuilder::new().id("newest").state(DownloadState::Done).stored("newest.mp4", 40).settled_at(now - Duration::hours(1)).build(),
+        ];
+
+        assert_eq!(ids(policy(0, 100).select_expired(&downloads, 180, now)), vec!["oldest", "newest"]);
+    }
+}
@@ -0,0 +1,85 @@
+use std::error::Error;
+use std::time::Duration;
+
+use crate::envconfig::Envconfig;
+
+#[derive(Envconfig)]
+pub struct RetryPolicyCfg {
+    #[envconfig(from = "DOWNLOAD_MAX_ATTEMPTS", default = "3")]
+    pub max_attempts: u32,
+
+    #[envconfig(from = "DOWNLOAD_RETRY_BACKOFF_MS", default = "2000")]
+    pub initial_backoff_ms: u64,
+
+    #[envconfig(from = "DOWNLOAD_RETRY_MAX_BACKOFF_MS", default = "60000")]
+    pub max_backoff_ms: u64,
+}
+
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(cfg: &RetryPolicyCfg) -> Self {
+        Self {
+            max_attempts: cfg.max_attempts.max(1),
+            initial_backoff: Duration::from_millis(cfg.initial_backoff_ms),
+            max_backoff: Duration::from_millis(cfg.max_backoff_ms),
+        }
+    }
+
+    pub fn new_from_env() -> Result<Self, Box<dyn Error>> {
+        let retry_policy_cfg = RetryPolicyCfg::init_from_env()?;
+        Ok(Self::new(&retry_policy_cfg))
+    }
+
+    pub fn should_retry(&self, attempt: u32) -> bool {
+        attempt < self.max_attempts
+    }
+
+    /// Exponential backoff after the given (1-based) attempt failed.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::retry_policy::{RetryPolicy, RetryPolicyCfg};
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy::new(&RetryPolicyCfg {
+            max_attempts: 3,
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 5000,
+        })
+    }
+
+    #[test]
+    fn test_backoff_is_exponential_and_capped() {
+        let policy = policy();
+
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(4), Duration::from_secs(5));
+        assert_eq!(policy.backoff(64), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_should_retry() {
+        let policy = policy();
+
+        assert!(policy.should_retry(1));
+        assert!(policy.should_retry(2));
+        assert!(!policy.should_retry(3));
+    }
+}
@@ -0,0 +1,28 @@
+use std::error::Error;
+use std::time::Duration;
+
+use crate::envconfig::Envconfig;
+
+#[derive(Envconfig)]
+pub struct ShutdownPolicyCfg {
+    /// How long running downloads may take to finish once shutdown starts, before they are requeued
+    #[envconfig(from = "SHUTDOWN_DRAIN_TIMEOUT_SECS", default = "25")]
+    pub drain_timeout_secs: u64,
+}
+
+pub struct ShutdownPolicy {
+    pub drain_timeout: Duration,
+}
+
+impl ShutdownPolicy {
+    pub fn new(cfg: &ShutdownPolicyCfg) -> Self {
+        Self {
+            drain_timeout: Duration::from_secs(cfg.drain_timeout_secs),
+        }
+    }
+
+    pub fn new_from_env() -> Result<Self, Box<dyn Error>> {
+        let shutdown_policy_cfg = ShutdownPolicyCfg::init_from_env()?;
+        Ok(Self::new(&shutdown_policy_cfg))
+    }
+}
@@ -0,0 +1,67 @@
+use std::{
+    error::Error,
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio_util::sync::CancellationToken;
+
+use darklight_core::download_state::DownloadState;
+use darklight_persistence::repos::downloads::DownloadRepo;
+
+use crate::envconfig::Envconfig;
+
+#[derive(Envconfig)]
+pub struct StateGaugesJobCfg {
+    #[envconfig(from = "STATE_GAUGES_INTERVAL_SECS", default = "30")]
+    pub interval_secs: u64,
+}
+
+/// Keeps the gauges of stored downloads by state up to date. They are counted on an interval by the
+/// workers instead of on every scrape, as every process would otherwise query the same counts.
+pub struct StateGaugesJob {
+    cfg: Arc<StateGaugesJobCfg>,
+    download_repo: Arc<DownloadRepo>,
+}
+
+impl StateGaugesJob {
+    pub fn new(cfg: Arc<StateGaugesJobCfg>, download_repo: Arc<DownloadRepo>) -> Self {
+        Self { cfg, download_repo }
+    }
+
+    pub fn new_from_env(download_repo: Arc<DownloadRepo>) -> Result<Self, Box<dyn Error>> {
+        let cfg = Arc::new(StateGaugesJobCfg::init_from_env()?);
+        Ok(Self::new(cfg, download_repo))
+    }
+
+    pub async fn run(self: Arc<Self>, stop: CancellationToken) {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.cfg.interval_secs.max(1)));
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = stop.cancelled() => break,
+            }
+
+            // a database outage shouldn't clear the gauges, the last counts are kept instead
+            if let Err(e) = self.refresh().await {
+                eprintln!("failed to count downloads: {}", e)
+            }
+        }
+    }
+
+    async fn refresh(&self) -> Result<(), Box<dyn Error>> {
+        let counts = self.download_repo.count_by_state().await?;
+
+        darklight_metrics::DOWNLOADS_BY_STATE.reset();
+        darklight_metrics::QUEUE_DEPTH.set(0);
+        for (state, count) in counts {
+            if state == DownloadState::Initiated.as_str() {
+                darklight_metrics::QUEUE_DEPTH.set(count);
+            }
+            darklight_metrics::DOWNLOADS_BY_STATE.with_label_values(&[state.as_str()]).set(count);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+#[macro_use]
+extern crate lazy_static;
+
+use std::error::Error;
+
+use prometheus::{
+    exponential_buckets, register_histogram, register_histogram_vec, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec, Encoder, Histogram, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
+};
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+lazy_static! {
+    /// Labelled `requested`, `done`, `failed`, `cancelled` or `requeued`
+    pub static ref DOWNLOADS: IntCounterVec = register_int_counter_vec!(
+        "darklight_downloads_total",
+        "Downloads by outcome",
+        &["outcome"]
+    ).unwrap();
+
+    pub static ref DOWNLOAD_DURATION: HistogramVec = register_histogram_vec!(
+        "darklight_download_duration_seconds",
+        "Time from a worker picking up a download until it was settled, by outcome",
+        &["outcome"],
+        vec![1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1200.0, 1800.0, 3600.0]
+    ).unwrap();
+
+    pub static ref DOWNLOAD_BYTES: Histogram = register_histogram!(
+        "darklight_download_bytes",
+        "Size of finished downloads",
+        // 1 MiB up to 16 GiB
+        exponential_buckets(1024.0 * 1024.0, 4.0, 8).unwrap()
+    ).unwrap();
+
+    /// Labelled with the exit code, `signal` when yt-dlp was killed, `cancelled` or `spawn-failed`
+    pub static ref YTDLP_EXITS: IntCounterVec = register_int_counter_vec!(
+        "darklight_ytdlp_exits_total",
+        "yt-dlp runs by exit code",
+        &["code"]
+    ).unwrap();
+
+    pub static ref UPLOAD_DURATION: Histogram = register_histogram!(
+        "darklight_storage_upload_seconds",
+        "Time to upload a finished download to storage",
+        vec![0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0]
+    ).unwrap();
+
+    pub static ref PUBLISH_FAILURES: IntCounterVec = register_int_counter_vec!(
+        "darklight_event_publish_failures_total",
+        "Events which could not be published, by subject",
+        &["subject"]
+    ).unwrap();
+
+    /// `group` is empty for subscribers outside of a queue group, `outcome` is `ok` or `error`
+    pub static ref HANDLER_DURATION: HistogramVec = register_histogram_vec!(
+        "darklight_event_handler_seconds",
+        "Time to handle an event",
+        &["subject", "group", "outcome"]
+    ).unwrap();
+
+    /// Counted by every worker process, aggregate it with `max` rather than `sum`
+    pub static ref DOWNLOADS_BY_STATE: IntGaugeVec = register_int_gauge_vec!(
+        "darklight_downloads",
+        "Stored downloads by state",
+        &["state"]
+    ).unwrap();
+
+    /// Counted by every worker process, aggregate it with `max` rather than `sum`
+    pub static ref QUEUE_DEPTH: IntGauge = register_int_gauge!(
+        "darklight_queue_depth",
+        "Downloads waiting for a worker to start them"
+    ).unwrap();
+
+    pub static ref WORKER_IN_FLIGHT: IntGauge = register_int_gauge!(
+        "darklight_worker_in_flight",
+        "Download messages held by the workers of this process"
+    ).unwrap();
+
+    pub static ref WORKER_RUNNING: IntGauge = register_int_gauge!(
+        "darklight_worker_running",
+        "yt-dlp processes running in this process"
+    ).unwrap();
+}
+
+/// Renders every registered metric in the Prometheus text format.
+pub fn encode() -> Result<String, Box<dyn Error>> {
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&prometheus::gather(), &mut buffer)?;
+
+    Ok(String::from_utf8(buffer)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{encode, DOWNLOADS};
+
+    #[test]
+    fn test_encode_includes_recorded_metrics() {
+        DOWNLOADS.with_label_values(&["requested"]).inc();
+
+        let encoded = encode().unwrap();
+
+        assert!(encoded.contains("darklight_downloads_total{outcome=\"requested\"}"));
+    }
+}
@@ -31,4 +31,16 @@ impl PostgresDb {
 
         Self::new(&postgres_cfg).await
     }
-}
\ No newline at end of file
+
+    /// Fails when no connection to the database can be used.
+    pub async fn ping(&self) -> Result<(), Box<dyn Error>> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    /// Waits for the connections in use to be given back, then closes every connection.
+    pub async fn close(&self) {
+        self.pool.close().await
+    }
+}
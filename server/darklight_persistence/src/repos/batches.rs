@@ -0,0 +1,114 @@
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::error::Error;
+use std::str::FromStr;
+use sqlx::PgConnection;
+use std::sync::Arc;
+
+use darklight_core::download::Download;
+use darklight_core::download_batch::DownloadBatch;
+
+use crate::postgres::PostgresDb;
+use crate::repos::downloads::DownloadRepo;
+use crate::repos::outbox::{OutboxMessage, OutboxRepo};
+
+pub struct BatchRepo {
+    db: Arc<PostgresDb>,
+}
+
+struct BatchDto {
+    #[allow(dead_code)]
+    id: i64,
+    batch_id: Uuid,
+    link: String,
+    title: Option<String>,
+    insert_time: DateTime<Utc>,
+    requester_id: Uuid,
+}
+
+impl From<BatchDto> for DownloadBatch {
+    fn from(b: BatchDto) -> Self {
+        DownloadBatch {
+            id: Some(b.batch_id.to_string()),
+            link: b.link,
+            title: b.title,
+            insert_time: Some(b.insert_time),
+            requester_id: Some(b.requester_id.to_string()),
+            downloads: vec![],
+        }
+    }
+}
+
+impl BatchRepo {
+    pub fn new(db: Arc<PostgresDb>) -> Self {
+        Self { db }
+    }
+
+    /// Adds the batch together with its downloads and the outbox messages `message` builds for the
+    /// downloads which need one, so a batch is never left half added.
+    pub async fn add_batch_with_downloads<F>(
+        &self,
+        batch: &DownloadBatch,
+        downloads: Vec<Download>,
+        message: F,
+    ) -> Result<DownloadBatch, Box<dyn Error>>
+    where
+        F: Fn(&Download) -> Result<Option<OutboxMessage>, Box<dyn Error>>,
+    {
+        let mut tx = self.db.pool.begin().await?;
+
+        let mut new_batch = Self::insert_batch(&mut tx, batch).await?;
+        for mut download in downloads {
+            download.batch_id = new_batch.id.clone();
+            let new_download = DownloadRepo::insert_download(&mut tx, &download).await?;
+            let message = message(&new_download)?;
+            if let Some(message) = message {
+                OutboxRepo::add_message(&mut tx, &message).await?;
+            }
+            new_batch.downloads.push(new_download);
+        }
+
+        tx.commit().await?;
+
+        Ok(new_batch)
+    }
+
+    async fn insert_batch(conn: &mut PgConnection, batch: &DownloadBatch) -> Result<DownloadBatch, Box<dyn Error>> {
+        let rec = sqlx::query_file!(
+            "src/repos/batches/add_batch.sql",
+            batch.link,
+            batch.title,
+            batch.insert_time,
+            Uuid::from_str(
+                batch
+                    .requester_id
+                    .as_ref()
+                    .ok_or("request id was not found")?
+                    .as_str()
+            )?,
+        )
+        .fetch_one(conn)
+        .await?;
+
+        let mut new_batch = batch.clone();
+        new_batch.id = Some(rec.batch_id.to_string());
+
+        Ok(new_batch)
+    }
+
+    pub async fn get_by_batch_id(
+        &self,
+        batch_id: &str,
+    ) -> Result<Option<DownloadBatch>, Box<dyn Error>> {
+        let mut conn = self.db.pool.acquire().await?;
+        let rec: Option<BatchDto> = sqlx::query_file_as!(
+            BatchDto,
+            "src/repos/batches/get_batch_by_batch_id.sql",
+            Uuid::from_str(batch_id)?
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        Ok(rec.map(DownloadBatch::from))
+    }
+}
@@ -0,0 +1,3 @@
+INSERT INTO download_batches (link, title, insert_time, requester_id)
+VALUES ($1, $2, $3, $4)
+RETURNING batch_id
@@ -0,0 +1,3 @@
+SELECT *
+FROM download_batches
+WHERE batch_id = $1
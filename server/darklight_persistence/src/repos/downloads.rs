@@ -1,19 +1,24 @@
 use sqlx::types::chrono::{DateTime, Utc};
 use sqlx::types::Uuid;
+use sqlx::{PgConnection, Postgres, Transaction};
 use std::error::Error;
 use std::str::FromStr;
 use std::sync::Arc;
 
-use darklight_core::download::Download;
+use darklight_core::download::{Download, DownloadMetadata, DownloadRun};
+use darklight_core::download_error::{DownloadError, DownloadErrorKind};
+use darklight_core::download_options::{AudioCodec, AudioExtraction, DownloadOptions, FormatPreset};
 use darklight_core::download_state::DownloadState;
 
 use crate::postgres::PostgresDb;
+use crate::repos::outbox::{OutboxMessage, OutboxRepo};
 
 pub struct DownloadRepo {
     db: Arc<PostgresDb>,
 }
 
 struct DownloadDto {
+    #[allow(dead_code)]
     id: i64,
     state: String,
     link: String,
@@ -22,6 +27,117 @@ struct DownloadDto {
     download_id: Uuid,
     percentage: Option<i64>,
     requester_id: Uuid,
+    title: Option<String>,
+    uploader: Option<String>,
+    duration: Option<i64>,
+    thumbnail: Option<String>,
+    format: String,
+    audio_codec: Option<String>,
+    audio_bitrate: Option<i64>,
+    batch_id: Option<Uuid>,
+    error_kind: Option<String>,
+    error_message: Option<String>,
+    attempts: i64,
+    file_size: Option<i64>,
+    pinned: bool,
+    #[allow(dead_code)]
+    expired_time: Option<DateTime<Utc>>,
+    canonical_link: Option<String>,
+    storage_key: Option<String>,
+    checksum: Option<String>,
+    update_time: Option<DateTime<Utc>>,
+}
+
+struct DownloadRunDto {
+    #[allow(dead_code)]
+    id: i64,
+    #[allow(dead_code)]
+    download_id: Uuid,
+    state: String,
+    file: Option<String>,
+    percentage: Option<i64>,
+    attempts: i64,
+    error_kind: Option<String>,
+    error_message: Option<String>,
+    insert_time: DateTime<Utc>,
+    archived_time: DateTime<Utc>,
+    file_size: Option<i64>,
+    storage_key: Option<String>,
+    checksum: Option<String>,
+}
+
+impl From<DownloadRunDto> for DownloadRun {
+    fn from(r: DownloadRunDto) -> Self {
+        DownloadRun {
+            state: DownloadState::from_string(r.state.as_str())
+                .expect("download_state should always be set"),
+            file: r.file,
+            percentage: r.percentage.unwrap_or(0) as u32,
+            attempts: r.attempts as u32,
+            error: to_download_error(r.error_kind, r.error_message),
+            insert_time: r.insert_time,
+            archived_time: r.archived_time,
+            file_size: r.file_size.map(|s| s as u64),
+            storage_key: r.storage_key,
+            checksum: r.checksum,
+        }
+    }
+}
+
+fn to_download_error(kind: Option<String>, message: Option<String>) -> Option<DownloadError> {
+    message.map(|message| DownloadError {
+        kind: kind
+            .as_deref()
+            .and_then(DownloadErrorKind::from_string)
+            .unwrap_or(DownloadErrorKind::Unknown),
+        message,
+    })
+}
+
+impl From<DownloadDto> for Download {
+    fn from(d: DownloadDto) -> Self {
+        let metadata = match (&d.title, &d.uploader, &d.duration, &d.thumbnail) {
+            (None, None, None, None) => None,
+            _ => Some(DownloadMetadata {
+                title: d.title,
+                uploader: d.uploader,
+                duration: d.duration.map(|d| d as u32),
+                thumbnail: d.thumbnail,
+            }),
+        };
+
+        Download {
+            id: Some(d.download_id.to_string()),
+            state: DownloadState::from_string(d.state.as_str())
+                .expect("download_state should always be set"),
+            link: d.link,
+            file: d.file,
+            insert_time: Some(d.insert_time),
+            percentage: d.percentage.unwrap_or(0) as u32,
+            requester_id: Some(d.requester_id.to_string()),
+            metadata,
+            options: DownloadOptions {
+                format: FormatPreset::from_string(d.format.as_str()).unwrap_or_default(),
+                audio: d
+                    .audio_codec
+                    .as_deref()
+                    .and_then(AudioCodec::from_string)
+                    .map(|codec| AudioExtraction {
+                        codec,
+                        bitrate: d.audio_bitrate.map(|b| b as u32),
+                    }),
+            },
+            batch_id: d.batch_id.map(|b| b.to_string()),
+            error: to_download_error(d.error_kind, d.error_message),
+            attempts: d.attempts as u32,
+            file_size: d.file_size.map(|s| s as u64),
+            pinned: d.pinned,
+            canonical_link: d.canonical_link,
+            storage_key: d.storage_key,
+            checksum: d.checksum,
+            update_time: d.update_time,
+        }
+    }
 }
 
 impl DownloadRepo {
@@ -36,6 +152,33 @@ impl DownloadRepo {
 
     pub async fn add_download(&self, download: &Download) -> Result<Download, Box<dyn Error>> {
         let mut conn = self.db.pool.acquire().await?;
+
+        Self::insert_download(&mut conn, download).await
+    }
+
+    /// Adds the download together with the outbox message `message` builds for it, so the
+    /// message is published eventually if and only if the download exists.
+    pub async fn add_download_with_outbox<F>(
+        &self,
+        download: &Download,
+        message: F,
+    ) -> Result<Download, Box<dyn Error>>
+    where
+        F: FnOnce(&Download) -> Result<OutboxMessage, Box<dyn Error>>,
+    {
+        let mut tx = self.db.pool.begin().await?;
+
+        let new_download = Self::insert_download(&mut tx, download).await?;
+        let message = message(&new_download)?;
+        OutboxRepo::add_message(&mut tx, &message).await?;
+
+        tx.commit().await?;
+
+        Ok(new_download)
+    }
+
+    pub(crate) async fn insert_download(conn: &mut PgConnection, download: &Download) -> Result<Download, Box<dyn Error>> {
+        let metadata = download.metadata.clone().unwrap_or_default();
         let rec = sqlx::query_file!(
             "src/repos/downloads/add_download.sql",
             download.state.as_str(),
@@ -49,8 +192,21 @@ impl DownloadRepo {
                     .ok_or("request id was not found")?
                     .as_str()
             )?,
+            metadata.title,
+            metadata.uploader,
+            metadata.duration.map(i64::from),
+            metadata.thumbnail,
+            download.options.format.to_string(),
+            download.options.audio.as_ref().map(|a| a.codec.as_str()),
+            download.options.audio.as_ref().and_then(|a| a.bitrate).map(i64::from),
+            download.batch_id.as_deref().map(Uuid::from_str).transpose()?,
+            i64::from(download.percentage),
+            download.file_size.map(|s| s as i64),
+            download.canonical_link,
+            download.storage_key,
+            download.checksum,
         )
-        .fetch_one(&mut conn)
+        .fetch_one(conn)
         .await?;
 
         let mut new_download = download.clone();
@@ -63,6 +219,9 @@ impl DownloadRepo {
         &self,
         download_id: &str,
         file_name: &str,
+        file_size: Option<u64>,
+        storage_key: Option<&str>,
+        checksum: Option<&str>,
     ) -> Result<(), Box<dyn Error>> {
         let mut conn = self.db.pool.acquire().await?;
 
@@ -70,6 +229,10 @@ impl DownloadRepo {
             "src/repos/downloads/finish_download.sql",
             DownloadState::Done.as_str(),
             file_name,
+            file_size.map(|s| s as i64),
+            storage_key,
+            checksum,
+            Utc::now(),
             sqlx::types::Uuid::from_str(download_id)?
         )
         .execute(&mut conn)
@@ -78,6 +241,280 @@ impl DownloadRepo {
         Ok(())
     }
 
+    pub async fn fail_download(
+        &self,
+        download_id: &str,
+        error: &DownloadError,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.db.pool.acquire().await?;
+
+        let _ = sqlx::query_file!(
+            "src/repos/downloads/fail_download.sql",
+            DownloadState::Error.as_str(),
+            error.kind.as_str(),
+            error.message.as_str(),
+            Utc::now(),
+            sqlx::types::Uuid::from_str(download_id)?
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Cancels a download which has not settled yet, together with the outbox message `message`
+    /// builds for it. Returns `None` when the download already settled.
+    pub async fn cancel_download_with_outbox<F>(&self, download_id: &str, message: F) -> Result<Option<Download>, Box<dyn Error>>
+    where
+        F: FnOnce(&Download) -> Result<OutboxMessage, Box<dyn Error>>,
+    {
+        let download_id = sqlx::types::Uuid::from_str(download_id)?;
+        let mut tx = self.db.pool.begin().await?;
+
+        let cancel = sqlx::query_file!(
+            "src/repos/downloads/cancel_download.sql",
+            DownloadState::Cancelled.as_str(),
+            Utc::now(),
+            download_id
+        )
+        .execute(&mut tx)
+        .await?;
+        if cancel.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let download: Download = sqlx::query_file_as!(
+            DownloadDto,
+            "src/repos/downloads/get_download_by_download_id.sql",
+            download_id
+        )
+        .fetch_one(&mut tx)
+        .await?
+        .into();
+        let message = message(&download)?;
+        OutboxRepo::add_message(&mut tx, &message).await?;
+
+        tx.commit().await?;
+
+        Ok(Some(download))
+    }
+
+    pub async fn expire_download(&self, download_id: &str) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.db.pool.acquire().await?;
+
+        let _ = sqlx::query_file!(
+            "src/repos/downloads/expire_download.sql",
+            DownloadState::Expired.as_str(),
+            Utc::now(),
+            sqlx::types::Uuid::from_str(download_id)?
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_pinned(&self, download_id: &str, pinned: bool) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.db.pool.acquire().await?;
+
+        let _ = sqlx::query_file!(
+            "src/repos/downloads/update_pinned.sql",
+            pinned,
+            sqlx::types::Uuid::from_str(download_id)?
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// The latest finished download of the same media with the same options, whose file can be shared.
+    pub async fn get_completed_by_canonical_link(
+        &self,
+        canonical_link: &str,
+        options: &DownloadOptions,
+    ) -> Result<Option<Download>, Box<dyn Error>> {
+        let mut conn = self.db.pool.acquire().await?;
+
+        let download = sqlx::query_file_as!(
+            DownloadDto,
+            "src/repos/downloads/get_completed_by_canonical_link.sql",
+            canonical_link,
+            options.format.to_string(),
+            options.audio.as_ref().map(|a| a.codec.as_str()),
+            options.audio.as_ref().and_then(|a| a.bitrate).map(i64::from),
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        Ok(download.map(|d| d.into()))
+    }
+
+    /// Locks the stored object `object_key` until the returned lock is committed or dropped, so
+    /// no download starts or stops referencing it while it is checked for or deleted.
+    pub async fn lock_object(&self, object_key: &str) -> Result<ObjectLock, Box<dyn Error>> {
+        let mut tx = self.db.pool.begin().await?;
+
+        let _ = sqlx::query_file!("src/repos/downloads/lock_object.sql", object_key)
+            .execute(&mut tx)
+            .await?;
+
+        Ok(ObjectLock {
+            tx,
+            object_key: object_key.to_string(),
+        })
+    }
+
+    /// Counts the stored downloads of every state which has any.
+    pub async fn count_by_state(&self) -> Result<Vec<(String, i64)>, Box<dyn Error>> {
+        let mut conn = self.db.pool.acquire().await?;
+
+        let recs = sqlx::query_file!("src/repos/downloads/count_by_state.sql")
+            .fetch_all(&mut conn)
+            .await?;
+
+        Ok(recs.into_iter().map(|rec| (rec.state, rec.count)).collect())
+    }
+
+    /// A page of at most `limit` finished downloads, oldest first, which the retention job may
+    /// expire. The page continues after the download settled at `after`, or starts at the oldest.
+    pub async fn get_retention_candidates(
+        &self,
+        after: Option<(DateTime<Utc>, &str)>,
+        limit: i64,
+    ) -> Result<Vec<Download>, Box<dyn Error>> {
+        let mut conn = self.db.pool.acquire().await?;
+        let (after_time, after_id) = match after {
+            Some((time, download_id)) => (Some(time), Some(Uuid::from_str(download_id)?)),
+            None => (None, None),
+        };
+
+        let downloads = sqlx::query_file_as!(
+            DownloadDto,
+            "src/repos/downloads/get_retention_candidates.sql",
+            after_time,
+            after_id,
+            limit
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(downloads.into_iter().map(|d| d.into()).collect())
+    }
+
+    /// Bytes taken up by the files of finished downloads, counting shared files once.
+    pub async fn get_stored_bytes(&self) -> Result<u64, Box<dyn Error>> {
+        let mut conn = self.db.pool.acquire().await?;
+
+        let rec = sqlx::query_file!("src/repos/downloads/get_stored_bytes.sql")
+            .fetch_one(&mut conn)
+            .await?;
+
+        Ok(rec.bytes as u64)
+    }
+
+    /// Locks `job` until the returned lock is released or dropped, or returns `None` when another
+    /// replica holds it already.
+    pub async fn try_lock_job(&self, job: &str) -> Result<Option<JobLock>, Box<dyn Error>> {
+        let mut conn = self.db.pool.acquire().await?;
+        let _ = sqlx::query_file!("src/repos/downloads/add_job_lock.sql", job)
+            .execute(&mut conn)
+            .await?;
+
+        let mut tx = self.db.pool.begin().await?;
+        let locked = sqlx::query_file!("src/repos/downloads/lock_job.sql", job)
+            .fetch_optional(&mut tx)
+            .await?;
+
+        Ok(locked.map(|_| JobLock { tx }))
+    }
+
+    /// Archives the current run into the download history and resets the download for a new run,
+    /// together with the outbox message `message` builds for the reset download.
+    pub async fn reset_download_with_outbox<F>(&self, download_id: &str, message: F) -> Result<Download, Box<dyn Error>>
+    where
+        F: FnOnce(&Download) -> Result<OutboxMessage, Box<dyn Error>>,
+    {
+        let mut tx = self.db.pool.begin().await?;
+
+        let _ = sqlx::query_file!(
+            "src/repos/downloads/archive_download.sql",
+            sqlx::types::Uuid::from_str(download_id)?,
+            Utc::now()
+        )
+        .execute(&mut tx)
+        .await?;
+        let download = Self::restart_download(&mut tx, download_id, message).await?;
+
+        tx.commit().await?;
+
+        Ok(download)
+    }
+
+    /// Resets a download which never really ran for a new run, together with the outbox message
+    /// `message` builds for it.
+    pub async fn requeue_download_with_outbox<F>(&self, download_id: &str, message: F) -> Result<Download, Box<dyn Error>>
+    where
+        F: FnOnce(&Download) -> Result<OutboxMessage, Box<dyn Error>>,
+    {
+        let mut tx = self.db.pool.begin().await?;
+
+        let download = Self::restart_download(&mut tx, download_id, message).await?;
+
+        tx.commit().await?;
+
+        Ok(download)
+    }
+
+    async fn restart_download<F>(conn: &mut PgConnection, download_id: &str, message: F) -> Result<Download, Box<dyn Error>>
+    where
+        F: FnOnce(&Download) -> Result<OutboxMessage, Box<dyn Error>>,
+    {
+        let download_id = sqlx::types::Uuid::from_str(download_id)?;
+
+        let reset = sqlx::query_file!(
+            "src/repos/downloads/reset_download.sql",
+            DownloadState::Initiated.as_str(),
+            Utc::now(),
+            download_id
+        )
+        .execute(&mut *conn)
+        .await?;
+        // only settled downloads are reset, another retry or worker may have gotten to it first
+        if reset.rows_affected() == 0 {
+            return Err("download is not settled".into());
+        }
+
+        let download: Download = sqlx::query_file_as!(
+            DownloadDto,
+            "src/repos/downloads/get_download_by_download_id.sql",
+            download_id
+        )
+        .fetch_one(&mut *conn)
+        .await?
+        .into();
+        let message = message(&download)?;
+        OutboxRepo::add_message(conn, &message).await?;
+
+        Ok(download)
+    }
+
+    pub async fn get_download_history(
+        &self,
+        download_id: &str,
+    ) -> Result<Vec<DownloadRun>, Box<dyn Error>> {
+        let mut conn = self.db.pool.acquire().await?;
+        let rec: Vec<DownloadRunDto> = sqlx::query_file_as!(
+            DownloadRunDto,
+            "src/repos/downloads/get_download_history.sql",
+            Uuid::from_str(download_id)?
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(rec.into_iter().map(DownloadRun::from).collect())
+    }
+
     pub async fn update_percentage(
         &self,
         download_id: &str,
@@ -88,6 +525,26 @@ impl DownloadRepo {
         let _ = sqlx::query_file!(
             "src/repos/downloads/update_percentage.sql",
             i64::from(percentage),
+            Utc::now(),
+            sqlx::types::Uuid::from_str(download_id)?
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_attempts(
+        &self,
+        download_id: &str,
+        attempts: u32,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.db.pool.acquire().await?;
+
+        let _ = sqlx::query_file!(
+            "src/repos/downloads/update_attempts.sql",
+            i64::from(attempts),
+            Utc::now(),
             sqlx::types::Uuid::from_str(download_id)?
         )
         .execute(&mut conn)
@@ -106,6 +563,22 @@ impl DownloadRepo {
         let _ = sqlx::query_file!(
             "src/repos/downloads/update_file_name.sql",
             file_name.as_str(),
+            Utc::now(),
+            sqlx::types::Uuid::from_str(download_id)?
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Renews the lease of a running download, as if a worker had reported on it.
+    pub async fn touch_download(&self, download_id: &str) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.db.pool.acquire().await?;
+
+        let _ = sqlx::query_file!(
+            "src/repos/downloads/touch_download.sql",
+            Utc::now(),
             sqlx::types::Uuid::from_str(download_id)?
         )
         .execute(&mut conn)
@@ -114,36 +587,54 @@ impl DownloadRepo {
         Ok(())
     }
 
+    /// Renews the lease of a download which is still stalled since `cutoff`. Returns false when
+    /// someone else reported on it or recovered it in the meantime.
+    pub async fn claim_stalled_download(&self, download_id: &str, cutoff: DateTime<Utc>) -> Result<bool, Box<dyn Error>> {
+        let mut conn = self.db.pool.acquire().await?;
+
+        let result = sqlx::query_file!(
+            "src/repos/downloads/claim_stalled_download.sql",
+            Utc::now(),
+            sqlx::types::Uuid::from_str(download_id)?,
+            cutoff
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Returns the running downloads nobody reported on since `cutoff`.
+    pub async fn get_stalled_downloads(
+        &self,
+        cutoff: DateTime<Utc>,
+    ) -> Result<Vec<Download>, Box<dyn Error>> {
+        let mut conn = self.db.pool.acquire().await?;
+        let rec: Vec<DownloadDto> = sqlx::query_file_as!(
+            DownloadDto,
+            "src/repos/downloads/get_stalled_downloads.sql",
+            cutoff
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(rec.into_iter().map(Download::from).collect())
+    }
+
     pub async fn get_by_download_id(
         &self,
         download_id: &str,
     ) -> Result<Option<Download>, Box<dyn Error>> {
         let mut conn = self.db.pool.acquire().await?;
-        let rec = sqlx::query_file!(
+        let rec: Option<DownloadDto> = sqlx::query_file_as!(
+            DownloadDto,
             "src/repos/downloads/get_download_by_download_id.sql",
             sqlx::types::Uuid::from_str(download_id)?
         )
         .fetch_optional(&mut conn)
         .await?;
 
-        match rec {
-            None => Ok(None),
-            Some(d) => {
-                let p = d.percentage.unwrap() as u32;
-                let download = Download {
-                    id: Some(d.download_id.to_string()),
-                    state: DownloadState::from_string(d.state.as_str())
-                        .expect("download_state should always be set"),
-                    link: d.link,
-                    file: d.file,
-                    insert_time: Some(d.insert_time),
-                    percentage: p,
-                    requester_id: Some(d.requester_id.to_string()),
-                };
-
-                Ok(Some(download))
-            }
-        }
+        Ok(rec.map(Download::from))
     }
 
     pub async fn get_downloads_by_requester(
@@ -162,17 +653,88 @@ impl DownloadRepo {
 
         let ds = rec
             .into_iter()
-            .map(|d| Download {
-                id: Some(d.download_id.to_string()),
-                state: DownloadState::from_string(d.state.as_str()).unwrap(),
-                link: d.link,
-                file: d.file,
-                insert_time: Some(d.insert_time),
-                percentage: d.percentage.unwrap().try_into().unwrap(),
-                requester_id: Some(d.requester_id.to_string()),
-            })
+            .map(Download::from)
             .collect::<Vec<Download>>();
 
         Ok(ds)
     }
+    pub async fn get_downloads_by_batch(
+        &self,
+        batch_id: &str,
+    ) -> Result<Vec<Download>, Box<dyn Error>> {
+        let mut conn = self.db.pool.acquire().await?;
+        let rec: Vec<DownloadDto> = sqlx::query_file_as!(
+            DownloadDto,
+            "src/repos/downloads/get_downloads_by_batch.sql",
+            Uuid::from_str(batch_id)?
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(rec.into_iter().map(Download::from).collect())
+    }
+}
+
+/// Holds the lock `DownloadRepo::lock_object` took, dropping it releases the lock without changes.
+pub struct ObjectLock {
+    tx: Transaction<'static, Postgres>,
+    object_key: String,
+}
+
+impl ObjectLock {
+    /// Counts the downloads other than `download_id` still referencing the object.
+    pub async fn count_references(&mut self, download_id: &str) -> Result<i64, Box<dyn Error>> {
+        let rec = sqlx::query_file!(
+            "src/repos/downloads/count_file_references.sql",
+            self.object_key,
+            sqlx::types::Uuid::from_str(download_id)?
+        )
+        .fetch_one(&mut self.tx)
+        .await?;
+
+        Ok(rec.count)
+    }
+
+    /// Makes `download_id` reference the object before it is finished, so it is counted right away.
+    pub async fn reference(&mut self, download_id: &str, checksum: &str) -> Result<(), Box<dyn Error>> {
+        let _ = sqlx::query_file!(
+            "src/repos/downloads/reference_object.sql",
+            self.object_key,
+            checksum,
+            sqlx::types::Uuid::from_str(download_id)?
+        )
+        .execute(&mut self.tx)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn commit(self) -> Result<(), Box<dyn Error>> {
+        self.tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Commits after the object was deleted, removing the lock along with it.
+    pub async fn commit_deleted(mut self) -> Result<(), Box<dyn Error>> {
+        let _ = sqlx::query_file!("src/repos/downloads/delete_object_lock.sql", self.object_key)
+            .execute(&mut self.tx)
+            .await?;
+        self.tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+/// Holds the lock `DownloadRepo::try_lock_job` took, dropping it releases the lock as well.
+pub struct JobLock {
+    tx: Transaction<'static, Postgres>,
+}
+
+impl JobLock {
+    pub async fn release(self) -> Result<(), Box<dyn Error>> {
+        self.tx.commit().await?;
+
+        Ok(())
+    }
 }
@@ -1,3 +1,5 @@
-INSERT INTO downloads (state, link, file, insert_time, requester_id)
-VALUES ($1, $2, $3, $4, $5)
-RETURNING download_id
+INSERT INTO downloads (state, link, file, insert_time, requester_id, title, uploader, duration, thumbnail, format,
+                       audio_codec, audio_bitrate, batch_id, percentage, file_size, canonical_link, storage_key,
+                       checksum)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
+RETURNING download_id
\ No newline at end of file
@@ -0,0 +1,3 @@
+INSERT INTO job_locks (job)
+VALUES ($1)
+ON CONFLICT (job) DO NOTHING
@@ -0,0 +1,6 @@
+INSERT INTO download_history (download_id, state, file, percentage, attempts, error_kind, error_message, insert_time,
+                              archived_time, file_size, storage_key, checksum)
+SELECT download_id, state, file, percentage, attempts, error_kind, error_message, insert_time, $2, file_size,
+       storage_key, checksum
+FROM downloads
+WHERE download_id = $1
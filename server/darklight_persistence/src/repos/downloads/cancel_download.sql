@@ -0,0 +1,5 @@
+UPDATE downloads
+SET state       = $1,
+    update_time = $2
+WHERE download_id = $3
+  AND state IN ('initiated', 'downloading')
@@ -0,0 +1,5 @@
+UPDATE downloads
+SET update_time = $1
+WHERE download_id = $2
+  AND state IN ('initiated', 'downloading')
+  AND COALESCE(update_time, insert_time) < $3
\ No newline at end of file
@@ -0,0 +1,3 @@
+SELECT state, COUNT(*) AS "count!"
+FROM downloads
+GROUP BY state
\ No newline at end of file
@@ -0,0 +1,11 @@
+SELECT (SELECT COUNT(*)
+        FROM downloads
+        WHERE COALESCE(storage_key, file) = $1
+          AND state != 'expired'
+          AND download_id != $2)
+           + (SELECT COUNT(*)
+              FROM download_history h
+                       JOIN downloads d ON d.download_id = h.download_id
+              WHERE COALESCE(h.storage_key, h.file) = $1
+                AND d.state != 'expired'
+                AND h.download_id != $2) AS "count!"
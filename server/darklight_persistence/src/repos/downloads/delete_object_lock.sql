@@ -0,0 +1,3 @@
+DELETE
+FROM object_locks
+WHERE object_key = $1
\ No newline at end of file
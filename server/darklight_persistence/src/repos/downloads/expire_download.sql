@@ -0,0 +1,4 @@
+UPDATE downloads
+SET state        = $1,
+    expired_time = $2
+WHERE download_id = $3
\ No newline at end of file
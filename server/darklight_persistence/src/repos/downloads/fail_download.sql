@@ -0,0 +1,7 @@
+UPDATE downloads
+SET state         = $1,
+    error_kind    = $2,
+    error_message = $3,
+    update_time   = $4
+WHERE download_id = $5
+  AND state <> 'cancelled'
\ No newline at end of file
@@ -1,4 +1,9 @@
 UPDATE downloads
-SET state = $1,
-    file  = $2
-WHERE download_id = $3
+SET state       = $1,
+    file        = $2,
+    file_size   = $3,
+    storage_key = $4,
+    checksum    = $5,
+    update_time = $6
+WHERE download_id = $7
+  AND state <> 'cancelled'
\ No newline at end of file
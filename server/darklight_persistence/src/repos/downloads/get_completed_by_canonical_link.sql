@@ -0,0 +1,10 @@
+SELECT *
+FROM downloads
+WHERE canonical_link = $1
+  AND format = $2
+  AND audio_codec IS NOT DISTINCT FROM $3
+  AND audio_bitrate IS NOT DISTINCT FROM $4
+  AND state = 'done'
+  AND file IS NOT NULL
+ORDER BY insert_time DESC
+LIMIT 1
\ No newline at end of file
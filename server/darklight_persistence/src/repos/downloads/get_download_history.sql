@@ -0,0 +1,4 @@
+SELECT *
+FROM download_history
+WHERE download_id = $1
+ORDER BY archived_time
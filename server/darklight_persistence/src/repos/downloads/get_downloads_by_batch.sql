@@ -0,0 +1,4 @@
+SELECT *
+FROM downloads
+WHERE batch_id = $1
+ORDER BY insert_time
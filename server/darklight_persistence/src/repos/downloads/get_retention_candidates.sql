@@ -0,0 +1,6 @@
+SELECT *
+FROM downloads
+WHERE state IN ('done', 'error', 'cancelled')
+  AND ($1::timestamptz IS NULL OR (COALESCE(update_time, insert_time), download_id) > ($1, $2))
+ORDER BY COALESCE(update_time, insert_time), download_id
+LIMIT $3
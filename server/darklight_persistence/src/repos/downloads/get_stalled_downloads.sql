@@ -0,0 +1,5 @@
+SELECT *
+FROM downloads
+WHERE state IN ('initiated', 'downloading')
+  AND COALESCE(update_time, insert_time) < $1
+ORDER BY insert_time
\ No newline at end of file
@@ -0,0 +1,5 @@
+SELECT COALESCE(SUM(file_size), 0)::INT8 AS "bytes!"
+FROM (SELECT DISTINCT ON (COALESCE(storage_key, file)) file_size
+      FROM downloads
+      WHERE state IN ('done', 'error', 'cancelled')
+        AND COALESCE(storage_key, file) IS NOT NULL) AS stored
@@ -0,0 +1,4 @@
+SELECT job
+FROM job_locks
+WHERE job = $1
+    FOR UPDATE SKIP LOCKED
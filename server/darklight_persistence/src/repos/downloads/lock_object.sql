@@ -0,0 +1,3 @@
+INSERT INTO object_locks (object_key)
+VALUES ($1)
+ON CONFLICT (object_key) DO UPDATE SET object_key = excluded.object_key
\ No newline at end of file
@@ -0,0 +1,4 @@
+UPDATE downloads
+SET storage_key = $1,
+    checksum    = $2
+WHERE download_id = $3
\ No newline at end of file
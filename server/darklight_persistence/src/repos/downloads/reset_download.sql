@@ -0,0 +1,13 @@
+UPDATE downloads
+SET state         = $1,
+    update_time   = $2,
+    percentage    = 0,
+    attempts      = 0,
+    file          = NULL,
+    file_size     = NULL,
+    storage_key   = NULL,
+    checksum      = NULL,
+    error_kind    = NULL,
+    error_message = NULL
+WHERE download_id = $3
+  AND state IN ('done', 'error', 'cancelled', 'expired')
@@ -0,0 +1,4 @@
+UPDATE downloads
+SET update_time = $1
+WHERE download_id = $2
+  AND state IN ('initiated', 'downloading')
\ No newline at end of file
@@ -0,0 +1,4 @@
+UPDATE downloads
+SET attempts    = $1,
+    update_time = $2
+WHERE download_id = $3
\ No newline at end of file
@@ -1,3 +1,5 @@
 UPDATE downloads
-SET percentage = $1
-WHERE download_id = $2
+SET percentage  = $1,
+    update_time = $2
+WHERE download_id = $3
+  AND state <> 'cancelled'
\ No newline at end of file
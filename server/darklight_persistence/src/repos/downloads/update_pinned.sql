@@ -0,0 +1,3 @@
+UPDATE downloads
+SET pinned = $1
+WHERE download_id = $2
\ No newline at end of file
@@ -1 +1,3 @@
-pub mod downloads;
\ No newline at end of file
+pub mod batches;
+pub mod downloads;
+pub mod outbox;
\ No newline at end of file
@@ -0,0 +1,128 @@
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::{PgConnection, Postgres, Transaction};
+use std::error::Error;
+use std::sync::Arc;
+
+use crate::postgres::PostgresDb;
+
+/// An event waiting to be published, written in the same transaction as the change it announces.
+#[derive(Clone, Debug)]
+pub struct OutboxMessage {
+    pub id: Option<i64>,
+    pub subject: String,
+    pub payload: Vec<u8>,
+    pub insert_time: Option<DateTime<Utc>>,
+}
+
+impl OutboxMessage {
+    pub fn new(subject: &str, payload: Vec<u8>) -> Self {
+        Self {
+            id: None,
+            subject: subject.to_string(),
+            payload,
+            insert_time: None,
+        }
+    }
+}
+
+struct OutboxMessageDto {
+    id: i64,
+    subject: String,
+    payload: Vec<u8>,
+    insert_time: DateTime<Utc>,
+    #[allow(dead_code)]
+    sent_time: Option<DateTime<Utc>>,
+}
+
+impl From<OutboxMessageDto> for OutboxMessage {
+    fn from(m: OutboxMessageDto) -> Self {
+        OutboxMessage {
+            id: Some(m.id),
+            subject: m.subject,
+            payload: m.payload,
+            insert_time: Some(m.insert_time),
+        }
+    }
+}
+
+pub struct OutboxRepo {
+    db: Arc<PostgresDb>,
+}
+
+impl OutboxRepo {
+    pub fn new(db: Arc<PostgresDb>) -> Self {
+        Self { db }
+    }
+
+    /// Adds `message` as part of the transaction `conn` belongs to.
+    pub(crate) async fn add_message(conn: &mut PgConnection, message: &OutboxMessage) -> Result<(), Box<dyn Error>> {
+        let _ = sqlx::query_file!(
+            "src/repos/outbox/add_outbox_message.sql",
+            message.subject,
+            message.payload,
+            Utc::now()
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Claims the oldest messages which have not been sent yet. Messages claimed by another relay
+    /// are skipped, so each message is only published by one of them.
+    pub async fn claim_pending(&self, limit: u32) -> Result<OutboxClaim, Box<dyn Error>> {
+        let mut tx = self.db.pool.begin().await?;
+        let rec: Vec<OutboxMessageDto> = sqlx::query_file_as!(
+            OutboxMessageDto,
+            "src/repos/outbox/get_pending_outbox_messages.sql",
+            i64::from(limit)
+        )
+        .fetch_all(&mut tx)
+        .await?;
+
+        Ok(OutboxClaim {
+            tx,
+            messages: rec.into_iter().map(OutboxMessage::from).collect(),
+        })
+    }
+
+    pub async fn delete_sent_before(&self, cutoff: DateTime<Utc>) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.db.pool.acquire().await?;
+
+        let _ = sqlx::query_file!(
+            "src/repos/outbox/delete_sent_outbox_messages.sql",
+            cutoff
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Messages locked by one relay until the claim is committed. Dropping the claim releases the
+/// messages which weren't marked as sent.
+pub struct OutboxClaim {
+    tx: Transaction<'static, Postgres>,
+    pub messages: Vec<OutboxMessage>,
+}
+
+impl OutboxClaim {
+    pub async fn mark_sent(&mut self, id: i64) -> Result<(), Box<dyn Error>> {
+        let _ = sqlx::query_file!(
+            "src/repos/outbox/mark_outbox_message_sent.sql",
+            Utc::now(),
+            id
+        )
+        .execute(&mut self.tx)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn commit(self) -> Result<(), Box<dyn Error>> {
+        self.tx.commit().await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,2 @@
+INSERT INTO outbox (subject, payload, insert_time)
+VALUES ($1, $2, $3)
\ No newline at end of file
@@ -0,0 +1,3 @@
+DELETE
+FROM outbox
+WHERE sent_time < $1
\ No newline at end of file
@@ -0,0 +1,6 @@
+SELECT *
+FROM outbox
+WHERE sent_time IS NULL
+ORDER BY id
+LIMIT $1
+FOR UPDATE SKIP LOCKED
\ No newline at end of file
@@ -0,0 +1,3 @@
+UPDATE outbox
+SET sent_time = $1
+WHERE id = $2
\ No newline at end of file
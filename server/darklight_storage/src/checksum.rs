@@ -0,0 +1,109 @@
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Hex encoded SHA-256 of the file, read in chunks so large files never have to fit in memory.
+pub async fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
+
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Objects are stored under the hash of their content, so different files never collide.
+pub fn content_key(checksum: &str) -> String {
+    format!("sha256/{}", checksum)
+}
+
+/// Passes the data through while hashing it, and fails the read at the end of the object
+/// if the content does not match the expected checksum.
+pub struct ChecksumReader<R> {
+    inner: R,
+    hasher: Option<Sha256>,
+    expected: String,
+}
+
+impl<R> ChecksumReader<R> {
+    pub fn new(inner: R, expected: String) -> Self {
+        Self {
+            inner,
+            hasher: Some(Sha256::new()),
+            expected,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ChecksumReader<R> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let requested = buf.remaining();
+
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+
+        let read = &buf.filled()[filled..];
+        if !read.is_empty() {
+            if let Some(hasher) = this.hasher.as_mut() {
+                hasher.update(read);
+            }
+        } else if requested > 0 {
+            if let Some(hasher) = this.hasher.take() {
+                let actual = hex::encode(hasher.finalize());
+                if actual != this.expected {
+                    return Poll::Ready(Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("checksum mismatch, expected: {}, actual: {}", this.expected, actual),
+                    )));
+                }
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use crate::checksum::{sha256_file, ChecksumReader};
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[tokio::test]
+    async fn test_sha256_file() {
+        let path = std::env::temp_dir().join(format!("darklight-checksum-{}", std::process::id()));
+        tokio::fs::write(&path, b"abc").await.unwrap();
+
+        let checksum = sha256_file(&path).await;
+        tokio::fs::remove_file(&path).await.unwrap();
+
+        assert_eq!(checksum.unwrap(), ABC_SHA256);
+    }
+
+    #[tokio::test]
+    async fn test_checksum_reader() {
+        let mut data = Vec::new();
+        let mut reader = ChecksumReader::new(&b"abc"[..], ABC_SHA256.into());
+        reader.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, b"abc".to_vec());
+
+        let mut reader = ChecksumReader::new(&b"abd"[..], ABC_SHA256.into());
+        assert!(reader.read_to_end(&mut Vec::new()).await.is_err());
+    }
+}
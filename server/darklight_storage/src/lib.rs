@@ -1,6 +1,16 @@
 extern crate envconfig;
 extern crate envconfig_derive;
 
-pub mod storage_uploader;
-pub mod storage_downloader;
+pub mod checksum;
+pub mod local_storage;
+pub mod s3_storage;
+pub mod storage_backend;
 
+use crate::envconfig::Envconfig;
+
+#[derive(Envconfig)]
+pub struct StorageCfg {
+    /// Either `s3` or `local`
+    #[envconfig(from = "STORAGE_BACKEND", default = "s3")]
+    pub backend: String,
+}
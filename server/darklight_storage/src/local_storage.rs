@@ -0,0 +1,294 @@
+use std::error::Error;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+
+use async_trait::async_trait;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::envconfig::Envconfig;
+use crate::storage_backend::{ByteRange, ObjectInfo, ObjectReader, StorageBackend, StoredObject};
+
+#[derive(Envconfig)]
+pub struct LocalStorageCfg {
+    #[envconfig(from = "LOCAL_STORAGE_PATH", default = "./target/storage")]
+    pub path: String,
+}
+
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub async fn new(cfg: LocalStorageCfg) -> Result<Self, Box<dyn Error>> {
+        println!("Bootstrapping local storage at: {}", cfg.path);
+        tokio::fs::create_dir_all(&cfg.path).await?;
+
+        Ok(Self {
+            root: PathBuf::from(cfg.path),
+        })
+    }
+
+    pub async fn new_from_env() -> Result<Self, Box<dyn Error>> {
+        let local_storage_cfg = LocalStorageCfg::init_from_env()?;
+        Self::new(local_storage_cfg).await
+    }
+
+    async fn write_with<F, Fut>(&self, key: &str, write: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnOnce(PathBuf) -> Fut,
+        Fut: std::future::Future<Output = std::io::Result<()>>,
+    {
+        let path = self.path_for(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        // write to a temporary file first, so readers never see a partial object
+        let tmp_path = path.with_file_name(format!(
+            ".{}.tmp",
+            path.file_name().unwrap().to_string_lossy()
+        ));
+        write(tmp_path.clone()).await?;
+        tokio::fs::rename(&tmp_path, &path).await?;
+
+        Ok(())
+    }
+
+    fn path_for(&self, key: &str) -> Result<PathBuf, Box<dyn Error>> {
+        let key_path = Path::new(key);
+        if key.is_empty() || !key_path.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(format!("invalid storage key: {}", key).into());
+        }
+
+        Ok(self.root.join(key_path))
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LocalStorage {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.write_with(key, |tmp_path| tokio::fs::write(tmp_path, data)).await
+    }
+
+    async fn put_file(&self, key: &str, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.write_with(key, |tmp_path| async move {
+            tokio::fs::copy(path, tmp_path).await.map(|_| ())
+        })
+        .await
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        let path = self.path_for(key)?;
+        match tokio::fs::read(path).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<ObjectInfo>, Box<dyn Error>> {
+        let path = self.path_for(key)?;
+        match tokio::fs::metadata(path).await {
+            Ok(metadata) => Ok(Some(to_object_info(&metadata))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn get_stream(&self, key: &str, range: Option<ByteRange>) -> Result<Option<StoredObject>, Box<dyn Error>> {
+        let path = self.path_for(key)?;
+        let mut file = match tokio::fs::File::open(path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let info = to_object_info(&file.metadata().await?);
+
+        let reader: ObjectReader = match range {
+            Some(ByteRange { start, end }) => {
+                file.seek(std::io::SeekFrom::Start(start)).await?;
+                Box::pin(file.take(end - start + 1))
+            }
+            None => Box::pin(file),
+        };
+
+        Ok(Some(StoredObject {
+            info,
+            range,
+            reader,
+        }))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Box<dyn Error>> {
+        let path = self.path_for(key)?;
+        match tokio::fs::remove_file(path).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Box<dyn Error>> {
+        let path = self.path_for(key)?;
+        match tokio::fs::metadata(path).await {
+            Ok(metadata) => Ok(metadata.is_file()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut keys = Vec::new();
+        let mut dirs = vec![self.root.clone()];
+
+        while let Some(dir) = dirs.pop() {
+            let mut entries = tokio::fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if entry.file_type().await?.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+
+                let key = to_key(&self.root, &path);
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                let is_tmp = file_name.starts_with('.') && file_name.ends_with(".tmp");
+                if !is_tmp && key.starts_with(prefix) {
+                    keys.push(key);
+                }
+            }
+        }
+
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn ping(&self) -> Result<(), Box<dyn Error>> {
+        if !tokio::fs::metadata(&self.root).await?.is_dir() {
+            return Err(format!("storage path is not a directory: {}", self.root.display()).into());
+        }
+
+        Ok(())
+    }
+}
+
+// files are only ever replaced as a whole, so size and modification time identify a version
+fn to_object_info(metadata: &std::fs::Metadata) -> ObjectInfo {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|m| m.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+
+    ObjectInfo {
+        size: metadata.len(),
+        etag: Some(format!("\"{:x}-{:x}\"", metadata.len(), modified)),
+    }
+}
+
+fn to_key(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap()
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use crate::local_storage::{LocalStorage, LocalStorageCfg};
+    use crate::storage_backend::{ByteRange, StorageBackend};
+
+    async fn storage(name: &str) -> LocalStorage {
+        let path = std::env::temp_dir().join(format!("darklight-storage-{}-{}", name, std::process::id()));
+        let _ = tokio::fs::remove_dir_all(&path).await;
+
+        LocalStorage::new(LocalStorageCfg {
+            path: path.to_string_lossy().to_string(),
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_put_get_delete() {
+        let storage = storage("put-get-delete").await;
+
+        assert!(!storage.exists("video.mp4").await.unwrap());
+        assert_eq!(storage.get("video.mp4").await.unwrap(), None);
+
+        storage.put("video.mp4", b"some-video").await.unwrap();
+        assert!(storage.exists("video.mp4").await.unwrap());
+        assert_eq!(storage.get("video.mp4").await.unwrap(), Some(b"some-video".to_vec()));
+
+        storage.delete("video.mp4").await.unwrap();
+        storage.delete("video.mp4").await.unwrap();
+        assert!(!storage.exists("video.mp4").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_put_file_get_stream() {
+        let storage = storage("put-file-get-stream").await;
+        let source = std::env::temp_dir().join(format!("darklight-storage-source-{}", std::process::id()));
+        tokio::fs::write(&source, b"some-audio").await.unwrap();
+
+        storage.put_file("audio.mp3", &source).await.unwrap();
+        let mut object = storage.get_stream("audio.mp3", None).await.unwrap().unwrap();
+        let mut data = Vec::new();
+        object.reader.read_to_end(&mut data).await.unwrap();
+
+        assert_eq!(object.info.size, 10);
+        assert_eq!(data, b"some-audio".to_vec());
+        assert!(storage.get_stream("missing.mp3", None).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_stream_range() {
+        let storage = storage("get-stream-range").await;
+        storage.put("audio.mp3", b"some-audio").await.unwrap();
+
+        let range = ByteRange { start: 5, end: 8 };
+        let mut object = storage.get_stream("audio.mp3", Some(range)).await.unwrap().unwrap();
+        let mut data = Vec::new();
+        object.reader.read_to_end(&mut data).await.unwrap();
+
+        assert_eq!(object.info.size, 10);
+        assert_eq!(object.range, Some(range));
+        assert_eq!(data, b"audi".to_vec());
+        assert_eq!(storage.head("audio.mp3").await.unwrap().unwrap().etag, object.info.etag);
+    }
+
+    #[tokio::test]
+    async fn test_list() {
+        let storage = storage("list").await;
+
+        storage.put("a/one.mp3", b"1").await.unwrap();
+        storage.put("a/two.mp3", b"2").await.unwrap();
+        storage.put("b.mp4", b"3").await.unwrap();
+
+        assert_eq!(storage.list("").await.unwrap(), vec!["a/one.mp3", "a/two.mp3", "b.mp4"]);
+        assert_eq!(storage.list("a/").await.unwrap(), vec!["a/one.mp3", "a/two.mp3"]);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_keys_outside_root() {
+        let storage = storage("outside-root").await;
+
+        assert!(storage.put("../escape.mp4", b"nope").await.is_err());
+        assert!(storage.get("/etc/passwd").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ping_fails_without_storage_path() {
+        let storage = storage("ping").await;
+        assert!(storage.ping().await.is_ok());
+
+        tokio::fs::remove_dir_all(&storage.root).await.unwrap();
+
+        assert!(storage.ping().await.is_err());
+    }
+}
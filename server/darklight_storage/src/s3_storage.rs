@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use async_trait::async_trait;
+use s3::{Bucket, Region};
+use s3::command::Command;
+use s3::creds::Credentials;
+use s3::request::Reqwest;
+use s3::request_trait::Request;
+use tokio::io::{AsyncRead, DuplexStream, ReadBuf};
+use tokio::sync::oneshot;
+
+use crate::envconfig::Envconfig;
+use crate::storage_backend::{content_disposition, ByteRange, ObjectInfo, StorageBackend, StoredObject};
+
+// size of the in-memory pipe between the S3 response and the reader
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Envconfig)]
+pub struct S3StorageCfg {
+    #[envconfig(from = "MINIO_ACCESS_KEY")]
+    pub access_key: String,
+
+    #[envconfig(from = "MINIO_SECRET")]
+    pub secret: String,
+
+    #[envconfig(from = "MINIO_URL")]
+    pub url: String,
+
+    /// URL clients reach the storage on, presigned links are signed against it. Defaults to `MINIO_URL`
+    #[envconfig(from = "MINIO_PUBLIC_URL")]
+    pub public_url: Option<String>,
+
+    #[envconfig(from = "MINIO_BUCKET", default = "downloads")]
+    pub bucket: String,
+}
+
+struct Storage {
+    region: Region,
+    credentials: Credentials,
+    bucket: String,
+}
+
+pub struct S3Storage {
+    bucket: Bucket,
+    // only used to presign, the signature covers the host so it can't be rewritten afterwards
+    public_bucket: Bucket,
+}
+
+impl S3Storage {
+    pub async fn new(cfg: S3StorageCfg) -> Result<Self, Box<dyn Error>> {
+        let storage = S3Storage::connect(&cfg, cfg.url.as_str())?;
+        let bucket = S3Storage::create_bucket(&storage)?;
+        let public_storage = S3Storage::connect(&cfg, cfg.public_url.as_deref().unwrap_or(cfg.url.as_str()))?;
+        let public_bucket = S3Storage::create_bucket(&public_storage)?;
+
+        if bucket.put_object("somefile.txt", "some-file".as_bytes()).await.is_err() {
+            panic!("{}", "could not put test file in bucket")
+        }
+
+        Ok(Self {
+            bucket,
+            public_bucket,
+        })
+    }
+
+    pub async fn new_from_env() -> Result<Self, Box<dyn Error>> {
+        let s3_storage_cfg = S3StorageCfg::init_from_env()?;
+        Self::new(s3_storage_cfg).await
+    }
+
+    fn connect(cfg: &S3StorageCfg, url: &str) -> Result<Storage, Box<dyn Error>> {
+        println!("Bootstrapping Minio storage");
+        let minio = Storage {
+            region: Region::Custom {
+                region: "".into(),
+                endpoint: url.to_string(),
+
+            },
+            credentials: Credentials {
+                access_key: Some(cfg.access_key.clone()),
+                secret_key: Some(cfg.secret.clone()),
+                security_token: None,
+                session_token: None,
+            },
+            bucket: cfg.bucket.clone(),
+        };
+
+        Ok(minio)
+    }
+
+    fn create_bucket(storage: &Storage) -> Result<Bucket, Box<dyn Error>> {
+        println!("Creating Minio bucket connection");
+        match Bucket::new(&storage.bucket, storage.region.clone(), storage.credentials.clone()) {
+            Ok(b) => Ok(b.with_path_style()),
+            Err(e) => Err(e.into())
+        }
+    }
+}
+
+#[async_trait]
+impl StorageBackend for S3Storage {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        match self.bucket.put_object(key, data).await {
+            Ok((_, 200)) => Ok(()),
+            Ok((_, _)) => Err("failed to upload file".into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn put_file(&self, key: &str, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut file = tokio::fs::File::open(path).await?;
+
+        // uploads in multipart chunks once the file is larger than a single chunk
+        match self.bucket.put_object_stream(&mut file, key).await? {
+            200 => Ok(()),
+            code => Err(format!("failed to upload file, status: {}", code).into()),
+        }
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        let (data, code) = self.bucket.get_object(format!("/{}", key)).await?;
+        if code != 200 {
+            Ok(None)
+        } else {
+            Ok(Some(data))
+        }
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<ObjectInfo>, Box<dyn Error>> {
+        match self.bucket.head_object(format!("/{}", key)).await? {
+            (head, 200) => Ok(Some(ObjectInfo {
+                size: head.content_length.unwrap_or_default() as u64,
+                etag: head.e_tag,
+            })),
+            (_, 404) => Ok(None),
+            (_, code) => Err(format!("failed to look up file, status: {}", code).into()),
+        }
+    }
+
+    async fn get_stream(&self, key: &str, range: Option<ByteRange>) -> Result<Option<StoredObject>, Box<dyn Error>> {
+        let info = match self.head(key).await? {
+            Some(info) => info,
+            None => return Ok(None),
+        };
+
+        let path = format!("/{}", key);
+        let (reader, mut writer) = tokio::io::duplex(STREAM_BUFFER_SIZE);
+        let (result_tx, result_rx) = oneshot::channel();
+        let bucket = self.bucket.clone();
+        tokio::spawn(async move {
+            let result = match range {
+                Some(ByteRange { start, end }) => {
+                    Reqwest::new(&bucket, &path, Command::GetObjectRange { start, end: Some(end) })
+                        .response_data_to_writer(&mut writer)
+                        .await
+                }
+                None => bucket.get_object_stream(&path, &mut writer).await,
+            };
+
+            let result = match result {
+                Ok(200) | Ok(206) => Ok(()),
+                Ok(code) => Err(format!("failed to stream file: {}, status: {}", path, code)),
+                Err(e) => Err(format!("failed to stream file: {}, {}", path, e)),
+            };
+            if let Err(e) = &result {
+                eprintln!("{}", e)
+            }
+            let _ = result_tx.send(result);
+        });
+
+        Ok(Some(StoredObject {
+            info,
+            range,
+            reader: Box::pin(StreamReader { inner: reader, result: result_rx }),
+        }))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Box<dyn Error>> {
+        match self.bucket.delete_object(format!("/{}", key)).await? {
+            (_, 200..=299) | (_, 404) => Ok(()),
+            (_, code) => Err(format!("failed to delete file, status: {}", code).into()),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Box<dyn Error>> {
+        match self.bucket.head_object(format!("/{}", key)).await? {
+            (_, 200) => Ok(true),
+            (_, 404) => Ok(false),
+            (_, code) => Err(format!("failed to look up file, status: {}", code).into()),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let results = self.bucket.list(prefix.to_string(), None).await?;
+
+        Ok(results
+            .into_iter()
+            .flat_map(|r| r.contents)
+            .map(|o| o.key)
+            .collect())
+    }
+
+    async fn ping(&self) -> Result<(), Box<dyn Error>> {
+        // a HEAD on the bucket itself, rust-s3 has no command for it but builds the same request
+        let (_, code) = Reqwest::new(&self.bucket, "/", Command::HeadObject).response_data(false).await?;
+        match code {
+            200 => Ok(()),
+            code => Err(format!("failed to reach bucket, status: {}", code).into()),
+        }
+    }
+
+    async fn presign_get(&self, key: &str, file_name: &str, expires_in_secs: u32) -> Result<String, Box<dyn Error>> {
+        let queries = HashMap::from([(
+            "response-content-disposition".to_string(),
+            content_disposition("attachment", file_name),
+        )]);
+
+        match self.public_bucket.presign_get(format!("/{}", key), expires_in_secs, Some(queries)) {
+            Ok(url) => Ok(url),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Reads what the S3 response was piped into, failing at the end when the response didn't finish,
+/// so a truncated file isn't mistaken for a complete one.
+struct StreamReader {
+    inner: DuplexStream,
+    result: oneshot::Receiver<Result<(), String>>,
+}
+
+impl AsyncRead for StreamReader {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        match Pin::new(&mut self.inner).poll_read(cx, buf) {
+            Poll::Ready(Ok(())) if buf.filled().len() == filled => {}
+            other => return other,
+        }
+
+        // the pipe ended, which only counts as the end of the file when the response completed
+        match Pin::new(&mut self.result).poll(cx) {
+            Poll::Ready(Ok(Ok(()))) => Poll::Ready(Ok(())),
+            Poll::Ready(Ok(Err(e))) => Poll::Ready(Err(io::Error::other(e))),
+            Poll::Ready(Err(_)) => Poll::Ready(Err(io::Error::other("file stream ended unexpectedly"))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::sync::oneshot;
+
+    use crate::s3_storage::{S3Storage, S3StorageCfg, StreamReader};
+
+    async fn read_stream(result: Result<(), String>) -> std::io::Result<Vec<u8>> {
+        let (reader, mut writer) = tokio::io::duplex(16);
+        let (result_tx, result_rx) = oneshot::channel();
+        tokio::spawn(async move {
+            writer.write_all(b"partial").await.unwrap();
+            drop(writer);
+            let _ = result_tx.send(result);
+        });
+
+        let mut data = Vec::new();
+        StreamReader { inner: reader, result: result_rx }.read_to_end(&mut data).await?;
+
+        Ok(data)
+    }
+
+    #[tokio::test]
+    async fn test_stream_reader_ends_with_completed_response() {
+        assert_eq!(read_stream(Ok(())).await.unwrap(), b"partial");
+    }
+
+    #[tokio::test]
+    async fn test_stream_reader_fails_with_failed_response() {
+        assert!(read_stream(Err("status: 500".into())).await.is_err());
+    }
+
+    #[test]
+    fn test_presigns_against_public_url() {
+        let cfg = S3StorageCfg {
+            access_key: "access".into(),
+            secret: "secret".into(),
+            url: "http://minio:9000".into(),
+            public_url: Some("https://files.example.com".into()),
+            bucket: "downloads".into(),
+        };
+
+        let storage = S3Storage::connect(&cfg, cfg.public_url.as_deref().unwrap()).unwrap();
+        let url = S3Storage::create_bucket(&storage).unwrap().presign_get("/key", 60, None).unwrap();
+
+        assert!(url.starts_with("https://files.example.com/downloads/key"));
+    }
+}
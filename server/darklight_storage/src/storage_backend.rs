@@ -0,0 +1,104 @@
+use std::error::Error;
+use std::path::Path;
+use std::pin::Pin;
+
+use async_trait::async_trait;
+use tokio::io::AsyncRead;
+
+pub type ObjectReader = Pin<Box<dyn AsyncRead + Send>>;
+
+/// An inclusive range of bytes within a stored object.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectInfo {
+    pub size: u64,
+    pub etag: Option<String>,
+}
+
+/// A stored object which is read lazily, so large files never have to fit in memory.
+pub struct StoredObject {
+    pub info: ObjectInfo,
+    /// The part of the object the reader yields, or `None` for the whole object.
+    pub range: Option<ByteRange>,
+    pub reader: ObjectReader,
+}
+
+/// Object storage for finished downloads, keyed by file name.
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<(), Box<dyn Error>>;
+
+    /// Streams the file at `path` into storage without reading it into memory.
+    async fn put_file(&self, key: &str, path: &Path) -> Result<(), Box<dyn Error>>;
+
+    /// Returns `None` if no object is stored under `key`.
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>>;
+
+    /// Returns `None` if no object is stored under `key`.
+    async fn head(&self, key: &str) -> Result<Option<ObjectInfo>, Box<dyn Error>>;
+
+    /// Returns `None` if no object is stored under `key`. The range must lie within the object.
+    async fn get_stream(&self, key: &str, range: Option<ByteRange>) -> Result<Option<StoredObject>, Box<dyn Error>>;
+
+    /// Deleting a missing object is not an error.
+    async fn delete(&self, key: &str) -> Result<(), Box<dyn Error>>;
+
+    async fn exists(&self, key: &str) -> Result<bool, Box<dyn Error>>;
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, Box<dyn Error>>;
+
+    /// Fails when the storage can't be reached.
+    async fn ping(&self) -> Result<(), Box<dyn Error>>;
+
+    /// Creates a URL which can be used to fetch the object directly from storage until it expires.
+    async fn presign_get(&self, key: &str, file_name: &str, expires_in_secs: u32) -> Result<String, Box<dyn Error>> {
+        let _ = (key, file_name, expires_in_secs);
+        Err("presigned links are not supported by this storage backend".into())
+    }
+}
+
+/// Builds a `Content-Disposition` value for `file_name`. The quoted name is an ASCII fallback for
+/// old clients, the full name is sent percent-encoded as well, see RFC 6266.
+pub fn content_disposition(disposition: &str, file_name: &str) -> String {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => format!("\\{}", c),
+            c if c.is_ascii() && !c.is_ascii_control() => c.to_string(),
+            _ => "_".to_string(),
+        })
+        .collect();
+    let encoded: String = file_name
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect();
+
+    format!("{}; filename=\"{}\"; filename*=UTF-8''{}", disposition, fallback, encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage_backend::content_disposition;
+
+    #[test]
+    fn test_content_disposition_escapes_file_name() {
+        assert_eq!(
+            content_disposition("attachment", "a \"quoted\" \\ name.mp4"),
+            "attachment; filename=\"a \\\"quoted\\\" \\\\ name.mp4\"; filename*=UTF-8''a%20%22quoted%22%20%5C%20name.mp4"
+        );
+        assert_eq!(
+            content_disposition("inline", "café.mp3"),
+            "inline; filename=\"caf_.mp3\"; filename*=UTF-8''caf%C3%A9.mp3"
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use darklight_core::download_options::AudioCodec;
+
+use crate::youtube_dl::Arg;
+
+#[derive(Clone, Debug)]
+pub struct AudioExtraction {
+    pub codec: AudioCodec,
+    /// Target bitrate in kbit/s, ignored by lossless formats.
+    pub bitrate: Option<u32>,
+}
+
+impl AudioExtraction {
+    pub fn new(codec: AudioCodec, bitrate: Option<u32>) -> Self {
+        Self { codec, bitrate }
+    }
+
+    pub fn args(&self) -> Vec<Arg> {
+        let mut args = vec![
+            Arg::new("--extract-audio"),
+            Arg::new_with_args("--audio-format", self.codec.as_str()),
+        ];
+
+        if let Some(bitrate) = self.bitrate {
+            if self.codec != AudioCodec::Flac {
+                args.push(Arg::new_with_args("--audio-quality", format!("{}K", bitrate).as_str()));
+            }
+        }
+
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use darklight_core::download_options::AudioCodec;
+
+    use crate::audio_extraction::AudioExtraction;
+
+    #[test]
+    fn test_audio_extraction_args() {
+        let args = AudioExtraction::new(AudioCodec::Mp3, Some(192))
+            .args()
+            .iter()
+            .map(|a| a.to_string())
+            .collect::<Vec<String>>();
+
+        assert_eq!(args, vec!["--extract-audio", "--audio-format mp3", "--audio-quality 192K"]);
+    }
+
+    #[test]
+    fn test_audio_extraction_args_lossless_ignores_bitrate() {
+        let args = AudioExtraction::new(AudioCodec::Flac, Some(320)).args();
+
+        assert_eq!(args.len(), 2);
+    }
+}
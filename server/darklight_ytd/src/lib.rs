@@ -1 +1,3 @@
+pub mod audio_extraction;
+pub mod media_info;
 pub mod youtube_dl;
@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MediaFormat {
+    pub format_id: String,
+    pub ext: Option<String>,
+    pub format_note: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub vcodec: Option<String>,
+    pub acodec: Option<String>,
+    pub filesize: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlaylistEntry {
+    #[serde(rename = "_type")]
+    pub entry_type: Option<String>,
+    pub ie_key: Option<String>,
+    pub id: String,
+    pub url: Option<String>,
+    pub title: Option<String>,
+    pub uploader: Option<String>,
+    pub duration: Option<f64>,
+    #[serde(default)]
+    pub entries: Vec<PlaylistEntry>,
+}
+
+impl PlaylistEntry {
+    /// The entries of a channel are its tabs, which are playlists of their own.
+    pub fn is_playlist(&self) -> bool {
+        self.entry_type.as_deref() == Some("playlist") || self.ie_key.as_deref() == Some("YoutubeTab")
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MediaInfo {
+    #[serde(rename = "_type")]
+    pub media_type: Option<String>,
+    pub id: String,
+    pub title: Option<String>,
+    pub uploader: Option<String>,
+    pub duration: Option<f64>,
+    pub thumbnail: Option<String>,
+    pub webpage_url: Option<String>,
+    pub extractor: Option<String>,
+    #[serde(default)]
+    pub formats: Vec<MediaFormat>,
+    #[serde(default)]
+    pub entries: Vec<PlaylistEntry>,
+}
+
+impl MediaInfo {
+    pub fn is_playlist(&self) -> bool {
+        self.media_type.as_deref() == Some("playlist")
+    }
+
+    pub fn duration_seconds(&self) -> Option<u32> {
+        self.duration.map(|d| d.round() as u32)
+    }
+}
+
+pub fn parse_media_info(json: &str) -> serde_json::Result<MediaInfo> {
+    serde_json::from_str::<MediaInfo>(json)
+}
+
+/// Splits playlist entries into videos and nested playlists. Nested playlists listed with their
+/// entries are flattened up to `depth` levels deep, the others are returned to be probed on their own.
+pub fn flatten_entries(entries: Vec<PlaylistEntry>, depth: usize) -> (Vec<PlaylistEntry>, Vec<PlaylistEntry>) {
+    let mut videos = vec![];
+    let mut nested = vec![];
+    for entry in entries {
+        if !entry.is_playlist() {
+            videos.push(entry);
+        } else if depth > 0 && !entry.entries.is_empty() {
+            let (entry_videos, entry_nested) = flatten_entries(entry.entries, depth - 1);
+            videos.extend(entry_videos);
+            nested.extend(entry_nested);
+        } else {
+            nested.push(entry);
+        }
+    }
+
+    (videos, nested)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::media_info::{flatten_entries, parse_media_info};
+
+    #[test]
+    fn test_parse_media_info() {
+        let info = parse_media_info(r#"{
+            "id": "tv8-4bn1Lr8",
+            "title": "10 Design Patterns Explained in 10 Minutes",
+            "uploader": "Fireship",
+            "duration": 660.4,
+            "thumbnail": "https://i.ytimg.com/vi/tv8-4bn1Lr8/maxresdefault.jpg",
+            "webpage_url": "https://www.youtube.com/watch?v=tv8-4bn1Lr8",
+            "extractor": "youtube",
+            "formats": [
+                {"format_id": "140", "ext": "m4a", "vcodec": "none", "acodec": "mp4a.40.2", "filesize": 10682514},
+                {"format_id": "22", "ext": "mp4", "height": 720, "width": 1280}
+            ],
+            "some_field_we_ignore": true
+        }"#).unwrap();
+
+        assert_eq!(info.title, Some("10 Design Patterns Explained in 10 Minutes".into()));
+        assert_eq!(info.uploader, Some("Fireship".into()));
+        assert_eq!(info.duration_seconds(), Some(660));
+        assert_eq!(info.formats.len(), 2);
+        assert_eq!(info.formats[1].height, Some(720));
+    }
+
+    #[test]
+    fn test_parse_media_info_without_formats() {
+        let info = parse_media_info(r#"{"id": "abc", "title": null}"#).unwrap();
+
+        assert_eq!(info.title, None);
+        assert!(info.formats.is_empty());
+        assert!(!info.is_playlist());
+    }
+
+    #[test]
+    fn test_parse_media_info_flat_playlist() {
+        let info = parse_media_info(r#"{
+            "_type": "playlist",
+            "id": "PL0vfts4VzfNiI1BsIK5u7LpPaIDKMJIDN",
+            "title": "Design Patterns",
+            "entries": [
+                {"_type": "url", "ie_key": "Youtube", "id": "tv8-4bn1Lr8", "url": "https://www.youtube.com/watch?v=tv8-4bn1Lr8", "title": "10 Design Patterns Explained in 10 Minutes", "duration": 660.0},
+                {"_type": "url", "ie_key": "Youtube", "id": "v9ejT8FO-7I", "url": "https://www.youtube.com/watch?v=v9ejT8FO-7I", "title": null}
+            ]
+        }"#).unwrap();
+
+        assert!(info.is_playlist());
+        assert_eq!(info.entries.len(), 2);
+        assert_eq!(info.entries[1].url, Some("https://www.youtube.com/watch?v=v9ejT8FO-7I".into()));
+    }
+
+    #[test]
+    fn test_flatten_channel_entries() {
+        let info = parse_media_info(r#"{
+            "_type": "playlist",
+            "id": "UCsBjURrPoezykLs9EqgamOA",
+            "title": "Fireship",
+            "entries": [
+                {"_type": "url", "ie_key": "YoutubeTab", "id": "UCsBjURrPoezykLs9EqgamOA", "url": "https://www.youtube.com/channel/UCsBjURrPoezykLs9EqgamOA/videos", "title": "Fireship - Videos"},
+                {"_type": "playlist", "id": "UCsBjURrPoezykLs9EqgamOA", "title": "Fireship - Shorts", "entries": [
+                    {"_type": "url", "ie_key": "Youtube", "id": "ZzI9JE0i6Lc", "url": "https://www.youtube.com/shorts/ZzI9JE0i6Lc", "title": "Rust in 100 Seconds"},
+                    {"_type": "playlist", "id": "PL0vfts4VzfNiI1BsIK5u7LpPaIDKMJIDN", "title": "Design Patterns", "entries": [
+                        {"_type": "url", "ie_key": "Youtube", "id": "tv8-4bn1Lr8", "url": "https://www.youtube.com/watch?v=tv8-4bn1Lr8"}
+                    ]}
+                ]},
+                {"_type": "url", "ie_key": "Youtube", "id": "v9ejT8FO-7I", "url": "https://www.youtube.com/watch?v=v9ejT8FO-7I"}
+            ]
+        }"#).unwrap();
+
+        let (videos, nested) = flatten_entries(info.entries, 1);
+
+        let video_ids: Vec<&str> = videos.iter().map(|e| e.id.as_str()).collect();
+        let nested_titles: Vec<Option<&str>> = nested.iter().map(|e| e.title.as_deref()).collect();
+        assert_eq!(video_ids, vec!["ZzI9JE0i6Lc", "v9ejT8FO-7I"]);
+        assert_eq!(nested_titles, vec![Some("Fireship - Videos"), Some("Design Patterns")]);
+    }
+}
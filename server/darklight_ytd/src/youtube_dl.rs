@@ -10,6 +10,10 @@ use regex::Regex;
 use thiserror::Error;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+
+use crate::audio_extraction::AudioExtraction;
+use crate::media_info::{parse_media_info, MediaInfo};
 
 #[derive(Error, Debug)]
 pub enum YoutubeDLError {
@@ -19,6 +23,31 @@ pub enum YoutubeDLError {
     UTF8Error(#[from] std::string::FromUtf8Error),
     #[error("youtube-dl exited with: {0}")]
     Failure(String),
+    #[error("failed to parse youtube-dl json output")]
+    JsonError(#[from] serde_json::Error),
+    #[error("youtube-dl was cancelled")]
+    Cancelled,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FailureKind {
+    Cancelled,
+    UnsupportedUrl,
+    GeoBlocked,
+    PrivateVideo,
+    BotCheck,
+    Network,
+    Unknown,
+}
+
+impl YoutubeDLError {
+    pub fn failure_kind(&self) -> FailureKind {
+        match self {
+            YoutubeDLError::Failure(stderr) => classify_failure(stderr),
+            YoutubeDLError::Cancelled => FailureKind::Cancelled,
+            _ => FailureKind::Unknown,
+        }
+    }
 }
 
 type Result<T> = std::result::Result<T, YoutubeDLError>;
@@ -61,6 +90,7 @@ pub struct YoutubeDL {
     path: PathBuf,
     links: Vec<String>,
     args: Vec<Arg>,
+    cancellation: Option<CancellationToken>,
 }
 
 #[derive(Clone, Debug)]
@@ -70,9 +100,9 @@ pub struct YoutubeDLResult {
 }
 
 impl YoutubeDLResult {
-    fn new(path: &PathBuf) -> Self {
+    fn new(path: &Path) -> Self {
         Self {
-            path: path.clone(),
+            path: path.to_path_buf(),
             output: String::new(),
         }
     }
@@ -91,24 +121,34 @@ impl YoutubeDL {
         let path = Path::new(dl_path);
 
         if !path.exists() {
-            create_dir_all(&path)?;
+            create_dir_all(path)?;
         }
 
         if !path.is_dir() {
-            return Err(YoutubeDLError::IOError(std::io::Error::new(
-                std::io::ErrorKind::Other,
+            return Err(YoutubeDLError::IOError(std::io::Error::other(
                 "path is not a directory",
             )));
         }
 
         let path = canonicalize(dl_path)?;
-        Ok(YoutubeDL { path, links, args })
+        Ok(YoutubeDL { path, links, args, cancellation: None })
     }
 
     pub fn new(dl_path: &PathBuf, args: Vec<Arg>, link: &str) -> Result<YoutubeDL> {
         YoutubeDL::new_multiple_links(dl_path, args, vec![link.to_string()])
     }
 
+    pub fn extract_audio(mut self, audio_extraction: &AudioExtraction) -> Self {
+        self.args.extend(audio_extraction.args());
+        self
+    }
+
+    /// Kills the youtube-dl process once the token is cancelled.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
     pub async fn download<F, FutAvailable, FAvailable, Fut>(&self, progress_update_fn: F, file_name_available: FAvailable) -> Result<YoutubeDLResult>
         where
             F: Fn(u32) -> Fut,
@@ -116,7 +156,15 @@ impl YoutubeDL {
             Fut: Future<Output=()>,
             FutAvailable: Future<Output=()>
     {
-        let output = self.spawn_youtube_dl(progress_update_fn, file_name_available).await?;
+        let output = self.spawn_youtube_dl(progress_update_fn, file_name_available).await;
+        let exit = match &output {
+            Ok(output) => output.status.code().map(|code| code.to_string()).unwrap_or_else(|| "signal".to_string()),
+            Err(YoutubeDLError::Cancelled) => "cancelled".to_string(),
+            Err(_) => "spawn-failed".to_string(),
+        };
+        darklight_metrics::YTDLP_EXITS.with_label_values(&[exit.as_str()]).inc();
+
+        let output = output?;
         let mut result = YoutubeDLResult::new(&self.path);
 
         if !output.status.success() {
@@ -127,20 +175,37 @@ impl YoutubeDL {
         Ok(result)
     }
 
-    async fn spawn_youtube_dl<F, FutAvailable, FAvailable, Fut>(&self, progress_update_fn: F, file_name_available: FAvailable) -> Result<Output>
-        where
-            F: Fn(u32) -> Fut,
-            FAvailable: Fn(String) -> FutAvailable,
-            Fut: Future<Output=()>,
-            FutAvailable: Future<Output=()>
-    {
+    pub async fn probe(&self) -> Result<Vec<MediaInfo>> {
+        let mut cmd = self.command(&[
+            Arg::new("--dump-single-json"),
+            Arg::new("--simulate"),
+            Arg::new("--flat-playlist"),
+        ]);
+        // the caller may give up on a probe which hangs
+        cmd.kill_on_drop(true);
+
+        let output = cmd.output().await?;
+        if !output.status.success() {
+            return Err(YoutubeDLError::Failure(String::from_utf8(output.stderr)?));
+        }
+
+        let stdout = String::from_utf8(output.stdout)?;
+        let mut media_info = Vec::new();
+        for line in stdout.lines().filter(|l| !l.trim().is_empty()) {
+            media_info.push(parse_media_info(line)?);
+        }
+
+        Ok(media_info)
+    }
+
+    fn command(&self, extra_args: &[Arg]) -> Command {
         let mut cmd = Command::new(YOUTUBE_DL_COMMAND);
         cmd.current_dir(&self.path)
             .env("LC_ALL", "en_US.UTF-8")
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
-        for arg in self.args.iter() {
+        for arg in self.args.iter().chain(extra_args.iter()) {
             match &arg.input {
                 Some(input) => cmd.arg(&arg.arg).arg(input),
                 None => cmd.arg(&arg.arg),
@@ -148,24 +213,44 @@ impl YoutubeDL {
         }
 
         for link in self.links.iter() {
-            cmd.arg(&link);
+            cmd.arg(link);
         }
 
-        let mut pr = cmd.spawn()?;
+        cmd
+    }
+
+    async fn spawn_youtube_dl<F, FutAvailable, FAvailable, Fut>(&self, progress_update_fn: F, file_name_available: FAvailable) -> Result<Output>
+        where
+            F: Fn(u32) -> Fut,
+            FAvailable: Fn(String) -> FutAvailable,
+            Fut: Future<Output=()>,
+            FutAvailable: Future<Output=()>
+    {
+        let mut pr = self.command(&[]).kill_on_drop(true).spawn()?;
+        let cancellation = self.cancellation.clone().unwrap_or_default();
 
         {
             let stdout = pr.stdout.as_mut().unwrap();
             let stdout_reader = BufReader::new(stdout);
             let mut stdout_lines = stdout_reader.lines();
 
-            let mut have_gotten_file_name = false;
-            while let Ok(Some(line)) = stdout_lines.next_line().await {
+            let mut last_file_name: Option<String> = None;
+            loop {
+                let line = tokio::select! {
+                    line = stdout_lines.next_line() => line,
+                    _ = cancellation.cancelled() => break,
+                };
+                let line = match line {
+                    Ok(Some(line)) => line,
+                    _ => break,
+                };
                 println!("{}", line.clone());
 
-                if !have_gotten_file_name {
-                    if let Some(file_name) = parse_file_name(line.clone()) {
-                        file_name_available(file_name).await;
-                        have_gotten_file_name = true
+                // post processors (audio extraction, merging) rename the file, so the last name wins
+                if let Some(file_name) = parse_file_name(line.clone()) {
+                    if last_file_name.as_ref() != Some(&file_name) {
+                        file_name_available(file_name.clone()).await;
+                        last_file_name = Some(file_name)
                     }
                 }
 
@@ -175,10 +260,34 @@ impl YoutubeDL {
             }
         }
 
+        if cancellation.is_cancelled() {
+            pr.kill().await?;
+            return Err(YoutubeDLError::Cancelled);
+        }
+
         Ok(pr.wait_with_output().await?)
     }
 }
 
+/// Returns the version of the installed yt-dlp, failing when it can't be run.
+pub async fn version() -> Result<String> {
+    let output = Command::new(YOUTUBE_DL_COMMAND).arg("--version").output().await?;
+    if !output.status.success() {
+        return Err(YoutubeDLError::Failure(String::from_utf8(output.stderr)?));
+    }
+
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
+/// Looks up yt-dlp on the `PATH` the way running it would, without running it.
+pub fn binary_path() -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(YOUTUBE_DL_COMMAND))
+        .find(|path| path.is_file())
+}
+
 fn parse_line(line: String) -> Option<core::result::Result<u32, ParseIntError>> {
     lazy_static! {
         static ref RE: Regex = Regex::new(r"\[download\]\s+(\d+)").unwrap();
@@ -194,20 +303,43 @@ fn parse_line(line: String) -> Option<core::result::Result<u32, ParseIntError>>
 
 fn parse_file_name(line: String) -> Option<String> {
     lazy_static! {
-        static ref RE: Regex = Regex::new(r"^\[download\] Destination: (.+)$").unwrap();
+        static ref RE: Regex = Regex::new(r#"^(?:\[(?:download|ExtractAudio)\] Destination: (.+)|\[Merger\] Merging formats into "(.+)")$"#).unwrap();
     }
 
     let capture: regex::Captures = RE.captures(line.as_str())?;
-    if capture.len() != 2 {
-        return None;
+    capture
+        .get(1)
+        .or_else(|| capture.get(2))
+        .map(|m| m.as_str().to_string())
+}
+
+fn classify_failure(stderr: &str) -> FailureKind {
+    lazy_static! {
+        static ref UNSUPPORTED: Regex = Regex::new(r"(?i)unsupported url|is not a valid url").unwrap();
+        static ref GEO_BLOCKED: Regex = Regex::new(r"(?i)available in your country|geo.?restrict").unwrap();
+        static ref PRIVATE: Regex = Regex::new(r"(?i)private video|video is private|members.only").unwrap();
+        static ref BOT_CHECK: Regex = Regex::new(r"(?i)sign in to confirm you.re not a bot").unwrap();
+        static ref NETWORK: Regex = Regex::new(r"(?i)http error 5\d\d|timed out|connection (reset|refused)|unable to download webpage|name resolution|network is unreachable").unwrap();
+    }
+
+    if UNSUPPORTED.is_match(stderr) {
+        FailureKind::UnsupportedUrl
+    } else if GEO_BLOCKED.is_match(stderr) {
+        FailureKind::GeoBlocked
+    } else if BOT_CHECK.is_match(stderr) {
+        FailureKind::BotCheck
+    } else if PRIVATE.is_match(stderr) {
+        FailureKind::PrivateVideo
+    } else if NETWORK.is_match(stderr) {
+        FailureKind::Network
+    } else {
+        FailureKind::Unknown
     }
-    let str = &capture[1];
-    Some(str.to_string())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::youtube_dl::{parse_file_name, parse_line};
+    use crate::youtube_dl::{classify_failure, parse_file_name, parse_line, FailureKind};
 
     #[test]
     fn test_parse_line() {
@@ -231,10 +363,40 @@ mod tests {
         assert_eq!(file_name, Some("10 Design Patterns Explained in 10 Minutes.mp4".into()));
     }
 
+    #[test]
+    fn test_parse_file_name_extract_audio() {
+        let file_name = parse_file_name("[ExtractAudio] Destination: 10 Design Patterns Explained in 10 Minutes.mp3".into());
+
+        assert_eq!(file_name, Some("10 Design Patterns Explained in 10 Minutes.mp3".into()));
+    }
+
+    #[test]
+    fn test_parse_file_name_merger() {
+        let file_name = parse_file_name("[Merger] Merging formats into \"10 Design Patterns Explained in 10 Minutes.mkv\"".into());
+
+        assert_eq!(file_name, Some("10 Design Patterns Explained in 10 Minutes.mkv".into()));
+    }
+
     #[test]
     fn test_parse_file_name_get_nothing() {
         let nothing = parse_file_name("[download] No fit: something".into());
 
         assert_eq!(nothing, None)
     }
+
+    #[test]
+    fn test_classify_failure() {
+        let cases = [
+            ("ERROR: Unsupported URL: https://example.com/", FailureKind::UnsupportedUrl),
+            ("ERROR: [youtube] abc: The uploader has not made this video available in your country", FailureKind::GeoBlocked),
+            ("ERROR: [youtube] abc: Private video. Sign in if you've been granted access to this video", FailureKind::PrivateVideo),
+            ("ERROR: [youtube] dQw4w9WgXcQ: Sign in to confirm you’re not a bot. Use --cookies-from-browser or --cookies for the authentication. See  https://github.com/yt-dlp/yt-dlp/wiki/FAQ#how-do-i-pass-cookies-to-yt-dlp  for how to manually pass cookies. Also see  https://github.com/yt-dlp/yt-dlp/wiki/Extractors#exporting-youtube-cookies  for tips on effectively exporting YouTube cookies", FailureKind::BotCheck),
+            ("ERROR: [youtube] abc: Unable to download webpage: HTTP Error 503: Service Unavailable", FailureKind::Network),
+            ("ERROR: Postprocessing: ffprobe and ffmpeg not found", FailureKind::Unknown),
+        ];
+
+        for (stderr, kind) in cases {
+            assert_eq!(classify_failure(stderr), kind, "{}", stderr);
+        }
+    }
 }